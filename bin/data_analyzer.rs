@@ -1,35 +1,51 @@
-use std::collections::HashSet;
+use std::collections::BTreeSet;
 
-use query::accouts::AllAccounts;
-use tokio::io::AsyncWriteExt;
+use clap::Parser;
+use query::{
+    accouts::AllAccounts,
+    envelope::{self, Header},
+    output::{self, Compression},
+};
+
+/// Diff two `all_accounts` JSON exports, plain or compressed.
+#[derive(Parser)]
+struct Args {
+    before: String,
+    after: String,
+    /// Compress the output with gzip or zstd, adding `.gz` or `.zst`.
+    #[arg(long, default_value = "none")]
+    compress: Compression,
+}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let before = tokio::fs::read_to_string("./all_accounts1668091204.json")
+    let args = Args::parse();
+
+    let before = output::read_file(&args.before)
         .await
         .expect("not found before file.");
-    let after = tokio::fs::read_to_string("./all_accounts1669365039.json")
+    let after = output::read_file(&args.after)
         .await
         .expect("not found after file.");
 
-    let before = serde_json::from_str::<AllAccounts>(&before)?;
-    let after = serde_json::from_str::<AllAccounts>(&after)?;
+    let before = envelope::open::<AllAccounts>(&before, "all_accounts")?;
+    let after = envelope::open::<AllAccounts>(&after, "all_accounts")?;
 
-    let mut surplus = HashSet::new();
+    let mut surplus = BTreeSet::new();
 
-    for a in before.accounts.iter() {
-        if !after.accounts.contains(&a) {
+    for a in before.data.accounts.iter() {
+        if !after.data.accounts.contains(&a) {
             surplus.insert(a.clone());
         }
     }
 
-    for a in after.accounts.into_iter() {
-        if !before.accounts.contains(&a) {
+    for a in after.data.accounts.into_iter() {
+        if !before.data.accounts.contains(&a) {
             surplus.insert(a);
         }
     }
 
-    let mut all = HashSet::new();
+    let mut all = BTreeSet::new();
 
     for a in surplus.into_iter() {
         for d in a.domains {
@@ -44,13 +60,16 @@ async fn main() -> anyhow::Result<()> {
     //     accounts: surplus,
     // };
 
-    let surplus_accounts_name = format!(
-        "surplus_accounts{}.json",
-        time::OffsetDateTime::now_utc().unix_timestamp()
-    );
-
-    let mut file = tokio::fs::File::create(surplus_accounts_name).await?;
-    file.write_all(&serde_json::to_vec_pretty(&all)?).await?;
+    let header = Header {
+        dataset: "surplus_accounts".to_string(),
+        params: serde_json::json!({
+            "before": before.header.block_number,
+            "after": after.header.block_number,
+        }),
+        record_count: Some(all.len()),
+        tool_version: envelope::TOOL_VERSION.to_string(),
+        ..after.header
+    };
 
-    Ok(())
+    output::write_json(&header, &all, args.compress).await
 }
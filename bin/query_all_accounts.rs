@@ -1,18 +1,48 @@
-use query::accouts::QueryDomainsBuilder;
-use tokio::io::AsyncWriteExt;
+use clap::Parser;
+use query::{
+    accouts::QueryDomainsBuilder,
+    cache::CacheArgs,
+    envelope::Header,
+    output::{self, Compression, Format, NdjsonWriter},
+};
+
+/// Export every account with the names it owns.
+#[derive(Parser)]
+struct Args {
+    /// json, csv or parquet with one row per account/domain pair, or ndjson
+    /// with one account per line.
+    #[arg(long, default_value = "json")]
+    format: Format,
+    /// Compress the output with gzip or zstd, adding `.gz` or `.zst`.
+    #[arg(long, default_value = "none")]
+    compress: Compression,
+    #[command(flatten)]
+    cache: CacheArgs,
+}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let accounts = QueryDomainsBuilder::query().await;
+    let args = Args::parse();
+    args.cache.install()?;
+    let meta = args.cache.meta().await?;
+    let block = meta.block_number;
+
+    if args.format == Format::Ndjson {
+        let mut writer =
+            NdjsonWriter::create(&Header::new("all_accounts", &meta), args.compress).await?;
+        QueryDomainsBuilder::query_each(block, |account| writer.write(&account)).await?;
+
+        println!("accounts len:{}", writer.finish().await?);
+        return Ok(());
+    }
 
-    let all_accounts_name = format!(
-        "all_accounts{}.json",
-        time::OffsetDateTime::now_utc().unix_timestamp()
-    );
+    let accounts = QueryDomainsBuilder::query(block).await;
+    let header = Header::new("all_accounts", &meta).with_record_count(accounts.accounts_num);
 
-    let mut file = tokio::fs::File::create(all_accounts_name).await?;
-    file.write_all(&serde_json::to_vec_pretty(&accounts)?)
-        .await?;
+    match args.format {
+        Format::Parquet => output::write_parquet(&header, &accounts, args.compress).await?,
+        format => output::write_file(&header, &accounts, format, args.compress).await?,
+    }
 
     Ok(())
 }
@@ -1,19 +1,47 @@
-use query::new_accounts::QueryNewDomainsBuilder;
-use tokio::io::AsyncWriteExt;
+use clap::Parser;
+use query::{
+    cache::CacheArgs,
+    envelope::Header,
+    new_accounts::QueryNewDomainsBuilder,
+    output::{self, Compression, Format, NdjsonWriter},
+};
+
+/// Export accounts split by whether they held names before the cut-off.
+#[derive(Parser)]
+struct Args {
+    /// json, or ndjson with one account per line.
+    #[arg(long, default_value = "json")]
+    format: Format,
+    /// Compress the output with gzip or zstd, adding `.gz` or `.zst`.
+    #[arg(long, default_value = "none")]
+    compress: Compression,
+    #[command(flatten)]
+    cache: CacheArgs,
+}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let accounts = QueryNewDomainsBuilder::query().await;
+    let args = Args::parse();
+    args.cache.install()?;
+    let meta = args.cache.meta().await?;
+    let block = meta.block_number;
 
-    let all_accounts_name = format!(
-        "all_new_accounts{}.json",
-        time::OffsetDateTime::now_utc().unix_timestamp()
-    );
+    match args.format {
+        Format::Json => {}
+        Format::Ndjson => {
+            let mut writer =
+                NdjsonWriter::create(&Header::new("all_new_accounts", &meta), args.compress)
+                    .await?;
+            QueryNewDomainsBuilder::query_each(block, |account| writer.write(&account)).await?;
 
-    let mut file = tokio::fs::File::create(all_accounts_name).await?;
+            println!("accounts len:{}", writer.finish().await?);
+            return Ok(());
+        }
+        format => anyhow::bail!("{format} is not supported for new accounts"),
+    }
 
-    file.write_all(&serde_json::to_vec_pretty(&accounts)?)
-        .await?;
+    let accounts = QueryNewDomainsBuilder::query(block).await;
+    let header = Header::new("all_new_accounts", &meta).with_record_count(accounts.len());
 
-    Ok(())
+    output::write_json(&header, &accounts, args.compress).await
 }
@@ -0,0 +1,54 @@
+use clap::Parser;
+use query::{
+    cache::CacheArgs,
+    domain_events::DomainEventsBuilder,
+    envelope::Header,
+    output::{self, Compression, Format, NdjsonWriter},
+};
+
+/// Export the full event history of one domain: transfers, resolver changes,
+/// subdomains, records and NFT bindings, in block order.
+#[derive(Parser)]
+struct Args {
+    /// Domain id (`0x...`) or full name.
+    domain: String,
+    /// json, or ndjson with one event per line.
+    #[arg(long, default_value = "json")]
+    format: Format,
+    /// Compress the output with gzip or zstd, adding `.gz` or `.zst`.
+    #[arg(long, default_value = "none")]
+    compress: Compression,
+    #[command(flatten)]
+    cache: CacheArgs,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    args.cache.install()?;
+
+    let meta = args.cache.meta().await?;
+    let block = meta.block_number;
+    let header = Header::new("domain_events", &meta)
+        .with_params(serde_json::json!({ "domain": args.domain }));
+
+    match args.format {
+        Format::Json => {}
+        Format::Ndjson => {
+            let mut writer = NdjsonWriter::create(&header, args.compress).await?;
+            DomainEventsBuilder::query_each(&args.domain, block, |event| writer.write(&event))
+                .await?;
+
+            println!("domain events len:{}", writer.finish().await?);
+            return Ok(());
+        }
+        format => anyhow::bail!("{format} is not supported for domain events"),
+    }
+
+    let events = DomainEventsBuilder::query(&args.domain, block).await;
+
+    println!("domain events len:{}", events.len());
+
+    let header = header.with_record_count(events.len());
+    output::write_json(&header, &events, args.compress).await
+}
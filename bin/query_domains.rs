@@ -0,0 +1,51 @@
+use clap::Parser;
+use query::{
+    cache::CacheArgs,
+    domains::DomainsBuilder,
+    envelope::Header,
+    output::{self, Compression, Format, NdjsonWriter},
+    query_all_at_each,
+};
+
+/// Export every domain with its owner, resolver, parent and latest
+/// registration.
+#[derive(Parser)]
+struct Args {
+    /// json, csv, ndjson or parquet.
+    #[arg(long, default_value = "json")]
+    format: Format,
+    /// Compress the output with gzip or zstd, adding `.gz` or `.zst`.
+    #[arg(long, default_value = "none")]
+    compress: Compression,
+    #[command(flatten)]
+    cache: CacheArgs,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    args.cache.install()?;
+    let meta = args.cache.meta().await?;
+    let block = meta.block_number;
+
+    if args.format == Format::Ndjson {
+        let mut writer =
+            NdjsonWriter::create(&Header::new("domains", &meta), args.compress).await?;
+        query_all_at_each::<DomainsBuilder>(block, |domain| writer.write(&domain?)).await?;
+
+        println!("domains len:{}", writer.finish().await?);
+        return Ok(());
+    }
+
+    let domains = DomainsBuilder::query(block).await?;
+
+    println!("domains len:{}", domains.len());
+
+    let header = Header::new("domains", &meta).with_record_count(domains.len());
+    match args.format {
+        Format::Parquet => output::write_parquet(&header, &domains, args.compress).await?,
+        format => output::write_file(&header, &domains, format, args.compress).await?,
+    }
+
+    Ok(())
+}
@@ -1,35 +1,82 @@
-use query::{new_subdomains, query_all, token_list};
-use tokio::io::AsyncWriteExt;
+use clap::Parser;
+use query::{
+    cache::CacheArgs,
+    envelope::Header,
+    new_subdomains,
+    output::{self, Compression, Format, NdjsonWriter},
+    query_all_at, query_all_at_each,
+    token_list::{self, TokenList, TokenRow},
+};
+
+/// Export the token list and every `NewSubdomain` event.
+#[derive(Parser)]
+struct Args {
+    /// json writes both in one document, csv and ndjson write one file each.
+    #[arg(long, default_value = "json")]
+    format: Format,
+    /// Compress the output with gzip or zstd, adding `.gz` or `.zst`.
+    #[arg(long, default_value = "none")]
+    compress: Compression,
+    #[command(flatten)]
+    cache: CacheArgs,
+}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let token_list = query_all::<token_list::QueryTokenList>().await;
+    let args = Args::parse();
+    args.cache.install()?;
+    let meta = args.cache.meta().await?;
+    let block = meta.block_number;
+
+    if args.format == Format::Ndjson {
+        let mut writer =
+            NdjsonWriter::create(&Header::new("token_list", &meta), args.compress).await?;
+        query_all_at_each::<token_list::QueryTokenList>(block, |id| {
+            writer.write(&TokenRow { id: &id })
+        })
+        .await?;
+        println!("token_list len: {}", writer.finish().await?);
+
+        let mut writer =
+            NdjsonWriter::create(&Header::new("new_subdomain", &meta), args.compress).await?;
+        query_all_at_each::<new_subdomains::NewSubdomainQueryBuilder>(block, |event| {
+            writer.write(&event)
+        })
+        .await?;
+        println!("new_subdomain len: {}", writer.finish().await?);
+
+        return Ok(());
+    }
 
-    let new_subdomain = query_all::<new_subdomains::NewSubdomainQueryBuilder>().await;
+    let token_list = TokenList(query_all_at::<token_list::QueryTokenList>(block).await);
 
-    println!("token_list len: {}", token_list.len());
+    let new_subdomain = query_all_at::<new_subdomains::NewSubdomainQueryBuilder>(block).await;
+
+    println!("token_list len: {}", token_list.0.len());
 
     println!("new_subdomain len: {}", new_subdomain.len());
 
+    if args.format == Format::Csv {
+        let header = Header::new("token_list", &meta).with_record_count(token_list.0.len());
+        output::write_file(&header, &token_list, args.format, args.compress).await?;
+
+        let header = Header::new("new_subdomain", &meta).with_record_count(new_subdomain.len());
+        output::write_file(&header, &new_subdomain, args.format, args.compress).await?;
+        return Ok(());
+    }
+
+    let header =
+        Header::new("pns_info", &meta).with_record_count(token_list.0.len() + new_subdomain.len());
     let pns_info = PnsInfo {
         token_list,
         new_subdomain,
     };
 
-    let pns_info_name = format!(
-        "pns_info{}.json",
-        time::OffsetDateTime::now_utc().unix_timestamp()
-    );
-
-    let mut file = tokio::fs::File::create(pns_info_name).await?;
-    file.write_all(&serde_json::to_vec_pretty(&pns_info)?)
-        .await?;
-
-    Ok(())
+    output::write_json(&header, &pns_info, args.compress).await
 }
 
 #[derive(Debug, serde::Serialize)]
 pub struct PnsInfo {
-    token_list: Vec<String>,
+    token_list: TokenList,
     new_subdomain: Vec<new_subdomains::NewSubdomain>,
 }
@@ -1,25 +1,58 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
-use query::{query_all, registrations};
-use tokio::io::AsyncWriteExt;
+use clap::Parser;
+use query::{
+    cache::CacheArgs,
+    envelope::Header,
+    output::{self, Compression, Format, NdjsonWriter},
+    query_all_at, query_all_at_each,
+    registrations::{self, RecordRow, RecordsBuilder},
+};
+
+/// Export origin, expiry, capacity and children of every registration.
+#[derive(Parser)]
+struct Args {
+    /// json, csv, ndjson or parquet.
+    #[arg(long, default_value = "json")]
+    format: Format,
+    /// Compress the output with gzip or zstd, adding `.gz` or `.zst`.
+    #[arg(long, default_value = "none")]
+    compress: Compression,
+    #[command(flatten)]
+    cache: CacheArgs,
+}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    args.cache.install()?;
+    let meta = args.cache.meta().await?;
+    let block = meta.block_number;
+
+    if args.format == Format::Ndjson {
+        let mut writer =
+            NdjsonWriter::create(&Header::new("records", &meta), args.compress).await?;
+        query_all_at_each::<RecordsBuilder>(block, |(id, record)| {
+            writer.write(&RecordRow::new(&id, &record))
+        })
+        .await?;
+
+        println!("records len:{}", writer.finish().await?);
+        return Ok(());
+    }
+
     let query_records = registrations::Records(
-        IntoIterator::into_iter(query_all::<registrations::RecordsBuilder>().await)
-            .collect::<HashMap<_, _>>(),
+        IntoIterator::into_iter(query_all_at::<RecordsBuilder>(block).await)
+            .collect::<BTreeMap<_, _>>(),
     );
 
     println!("records len:{}", query_records.0.len());
 
-    let records_name = format!(
-        "records{}.json",
-        time::OffsetDateTime::now_utc().unix_timestamp()
-    );
-
-    let mut file = tokio::fs::File::create(records_name).await?;
-    file.write_all(&serde_json::to_vec_pretty(&query_records)?)
-        .await?;
+    let header = Header::new("records", &meta).with_record_count(query_records.0.len());
+    match args.format {
+        Format::Parquet => output::write_parquet(&header, &query_records, args.compress).await?,
+        format => output::write_file(&header, &query_records, format, args.compress).await?,
+    }
 
     Ok(())
 }
@@ -0,0 +1,70 @@
+use clap::Parser;
+use query::{
+    cache::CacheArgs,
+    envelope::Header,
+    output::{self, Compression, Format, NdjsonWriter},
+    query_all_at, query_all_at_each,
+    registration_events::{self, RegistrationEventsBuilder},
+};
+
+/// Export the registration, renewal and capacity history of every name.
+#[derive(Parser)]
+struct Args {
+    /// Only the domain with this id.
+    #[arg(long)]
+    domain: Option<String>,
+    /// json groups events into one history per name. csv, ndjson and
+    /// parquet write one event per row without the `old*` fields.
+    #[arg(long, default_value = "json")]
+    format: Format,
+    /// Compress the output with gzip or zstd, adding `.gz` or `.zst`.
+    #[arg(long, default_value = "none")]
+    compress: Compression,
+    #[command(flatten)]
+    cache: CacheArgs,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    args.cache.install()?;
+    let meta = args.cache.meta().await?;
+    let block = meta.block_number;
+
+    if args.format == Format::Ndjson && args.domain.is_none() {
+        let header = Header::new("registration_events", &meta);
+        let mut writer = NdjsonWriter::create(&header, args.compress).await?;
+        query_all_at_each::<RegistrationEventsBuilder>(block, |event| writer.write(&event)).await?;
+
+        println!("registration events len:{}", writer.finish().await?);
+        return Ok(());
+    }
+
+    let header = Header::new("registration_events", &meta)
+        .with_params(serde_json::json!({ "domain": args.domain }));
+    let events = match args.domain {
+        Some(domain) => RegistrationEventsBuilder::query_domain(&domain, block).await,
+        None => query_all_at::<RegistrationEventsBuilder>(block).await,
+    };
+
+    println!("registration events len:{}", events.len());
+
+    match args.format {
+        Format::Json => {}
+        Format::Parquet => {
+            let header = header.with_record_count(events.len());
+            output::write_parquet(&header, &events, args.compress).await?;
+            return Ok(());
+        }
+        format => {
+            let header = header.with_record_count(events.len());
+            output::write_file(&header, &events, format, args.compress).await?;
+            return Ok(());
+        }
+    }
+
+    let histories = registration_events::histories(events);
+    let header = header.with_record_count(histories.len());
+
+    output::write_json(&header, &histories, args.compress).await
+}
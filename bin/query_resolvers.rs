@@ -0,0 +1,47 @@
+use clap::Parser;
+use query::{
+    cache::CacheArgs,
+    envelope::Header,
+    output::{self, Compression, Format, NdjsonWriter},
+    resolvers,
+};
+
+/// Export the resolver records of every domain with a resolver.
+#[derive(Parser)]
+struct Args {
+    /// json, or ndjson with one resolver per line. Records are joined from
+    /// several queries, so lines are written once every page is in.
+    #[arg(long, default_value = "json")]
+    format: Format,
+    /// Compress the output with gzip or zstd, adding `.gz` or `.zst`.
+    #[arg(long, default_value = "none")]
+    compress: Compression,
+    #[command(flatten)]
+    cache: CacheArgs,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    args.cache.install()?;
+    let meta = args.cache.meta().await?;
+    let block = meta.block_number;
+    let records = resolvers::query_resolver_records(block).await;
+
+    println!("resolver records len:{}", records.len());
+
+    let header = Header::new("resolvers", &meta).with_record_count(records.len());
+
+    match args.format {
+        Format::Json => output::write_json(&header, &records, args.compress).await,
+        Format::Ndjson => {
+            let mut writer = NdjsonWriter::create(&header, args.compress).await?;
+            for record in records.iter() {
+                writer.write(record)?;
+            }
+            writer.finish().await?;
+            Ok(())
+        }
+        format => anyhow::bail!("{format} is not supported for resolvers"),
+    }
+}
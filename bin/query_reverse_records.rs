@@ -0,0 +1,61 @@
+use clap::Parser;
+use query::{
+    cache::CacheArgs,
+    envelope::Header,
+    output::{self, Compression, Format, NdjsonWriter},
+    reverse_records,
+};
+
+/// Export primary names, the stale ones among them and NFT names.
+#[derive(Parser)]
+struct Args {
+    /// json writes both in one document, ndjson writes `primary_names` and
+    /// `nft_names` with one name per line. Only the latest event per account
+    /// or domain is kept, so lines are written once every page is in.
+    #[arg(long, default_value = "json")]
+    format: Format,
+    /// Compress the output with gzip or zstd, adding `.gz` or `.zst`.
+    #[arg(long, default_value = "none")]
+    compress: Compression,
+    #[command(flatten)]
+    cache: CacheArgs,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    args.cache.install()?;
+    let meta = args.cache.meta().await?;
+    let block = meta.block_number;
+    let records = reverse_records::query_reverse_records(block).await;
+
+    println!("primary names len:{}", records.primary_names_num);
+    println!("stale primary names len:{}", records.stale_num);
+    println!("nft names len:{}", records.nft_names_num);
+
+    match args.format {
+        Format::Json => {
+            let header = Header::new("reverse_records", &meta)
+                .with_record_count(records.primary_names_num + records.nft_names_num);
+            output::write_json(&header, &records, args.compress).await
+        }
+        Format::Ndjson => {
+            let header =
+                Header::new("primary_names", &meta).with_record_count(records.primary_names_num);
+            let mut writer = NdjsonWriter::create(&header, args.compress).await?;
+            for name in records.primary_names.iter() {
+                writer.write(name)?;
+            }
+            writer.finish().await?;
+
+            let header = Header::new("nft_names", &meta).with_record_count(records.nft_names_num);
+            let mut writer = NdjsonWriter::create(&header, args.compress).await?;
+            for name in records.nft_names.iter() {
+                writer.write(name)?;
+            }
+            writer.finish().await?;
+            Ok(())
+        }
+        format => anyhow::bail!("{format} is not supported for reverse records"),
+    }
+}
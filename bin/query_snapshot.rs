@@ -0,0 +1,65 @@
+use clap::Parser;
+use query::{
+    cache::CacheArgs,
+    output::{self, Compression, Format},
+    snapshot::Snapshot,
+};
+
+/// Export domains, registrations, subdomain events, transfers and resolvers
+/// at one block.
+#[derive(Parser)]
+struct Args {
+    /// json, or sqlite with one normalized table per dataset.
+    #[arg(long, default_value = "sqlite")]
+    format: Format,
+    /// Compress the output with gzip or zstd, adding `.gz` or `.zst`.
+    #[arg(long, default_value = "none")]
+    compress: Compression,
+    /// A previous snapshot, in either format. Only what changed after its
+    /// block is fetched and merged into it.
+    #[arg(long)]
+    since: Option<String>,
+    #[command(flatten)]
+    cache: CacheArgs,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    args.cache.install()?;
+    let meta = args.cache.meta().await?;
+    let (snapshot, params) = match &args.since {
+        Some(path) => {
+            let previous = Snapshot::load(path).await?;
+            println!("since block: {}", previous.meta.block_number);
+            let params = serde_json::json!({ "since": previous.meta.block_number });
+            (Snapshot::query_since(previous, meta).await?, params)
+        }
+        None => (Snapshot::query(meta).await?, serde_json::Value::Null),
+    };
+    let header = snapshot.header().with_params(params);
+
+    println!("block: {}", snapshot.meta.block_number);
+    println!("domains len:{}", snapshot.domains.len());
+    println!("registrations len:{}", snapshot.registrations.0.len());
+    println!("subdomain events len:{}", snapshot.subdomain_events.len());
+    println!("transfers len:{}", snapshot.transfers.len());
+    println!("resolvers len:{}", snapshot.resolvers.len());
+
+    match args.format {
+        Format::Sqlite => {
+            let path = output::file_name("snapshot", args.format, Compression::None);
+            // replaced like the other exports, `--since` may have just read it
+            if tokio::fs::try_exists(&path).await? {
+                tokio::fs::remove_file(&path).await?;
+            }
+            snapshot.write_sqlite(&header, &path)?;
+            let path = output::compress_file(&path, args.compress).await?;
+            output::write_checksum(&path).await?;
+        }
+        Format::Json => output::write_json(&header, &snapshot, args.compress).await?,
+        format => anyhow::bail!("{format} is not supported for snapshots"),
+    }
+
+    Ok(())
+}
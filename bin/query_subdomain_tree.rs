@@ -0,0 +1,119 @@
+use clap::Parser;
+use query::{
+    cache::CacheArgs,
+    domains::DomainsBuilder,
+    envelope::Header,
+    new_subdomains::NewSubdomainQueryBuilder,
+    output::{self, Compression, Format, NdjsonWriter},
+    query_all_at,
+    subdomain_tree::SubdomainTree,
+};
+
+/// Export the domain hierarchy and check `subdomainCount` against it.
+#[derive(Parser)]
+struct Args {
+    /// Write a flat parent/child edge list instead of nested trees.
+    #[arg(long)]
+    edges: bool,
+    /// json writes tree, mismatches, orphans and cycles in one document,
+    /// ndjson writes `subdomain_tree` with one root or edge per line,
+    /// `subdomain_count_mismatches` and, when there are any,
+    /// `subdomain_orphans` and `subdomain_cycles`.
+    #[arg(long, default_value = "json")]
+    format: Format,
+    /// Compress the output with gzip or zstd, adding `.gz` or `.zst`.
+    #[arg(long, default_value = "none")]
+    compress: Compression,
+    #[command(flatten)]
+    cache: CacheArgs,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    args.cache.install()?;
+    let meta = args.cache.meta().await?;
+    let block = meta.block_number;
+
+    let domains = DomainsBuilder::query(block).await?;
+    let events = query_all_at::<NewSubdomainQueryBuilder>(block).await;
+    let tree = SubdomainTree::build(&domains, &events);
+
+    let mismatches = tree.count_mismatches();
+    println!("subdomainCount mismatches len:{}", mismatches.len());
+    let orphans = tree.orphans();
+    println!("orphans len:{}", orphans.len());
+    let cycles = tree.cycles();
+    println!("cycles len:{}", cycles.len());
+
+    match args.format {
+        Format::Json => {}
+        Format::Ndjson => {
+            let header = Header::new("subdomain_tree", &meta)
+                .with_params(serde_json::json!({ "edges": args.edges }));
+            if args.edges {
+                write_ndjson(header, &tree.edges(), args.compress).await?;
+            } else {
+                write_ndjson(header, &tree.nested(), args.compress).await?;
+            }
+
+            let header = Header::new("subdomain_count_mismatches", &meta);
+            write_ndjson(header, &mismatches, args.compress).await?;
+            if !orphans.is_empty() {
+                let header = Header::new("subdomain_orphans", &meta);
+                write_ndjson(header, &orphans, args.compress).await?;
+            }
+            if !cycles.is_empty() {
+                let header = Header::new("subdomain_cycles", &meta);
+                write_ndjson(header, &cycles, args.compress).await?;
+            }
+            return Ok(());
+        }
+        format => anyhow::bail!("{format} is not supported for the subdomain tree"),
+    }
+
+    let (tree, record_count) = if args.edges {
+        let edges = tree.edges();
+        let len = edges.len();
+        (serde_json::to_value(edges)?, len)
+    } else {
+        (serde_json::to_value(tree.nested())?, domains.len())
+    };
+    let header = Header::new("subdomain_tree", &meta)
+        .with_params(serde_json::json!({ "edges": args.edges }))
+        .with_record_count(record_count);
+
+    output::write_json(
+        &header,
+        &serde_json::json!({
+            "tree": tree,
+            "mismatches": mismatches,
+            "orphans": orphans,
+            "cycles": cycles,
+        }),
+        args.compress,
+    )
+    .await
+}
+
+/// One line per record, with the record count set up front and checked
+/// against what was written.
+async fn write_ndjson<T: serde::Serialize>(
+    header: Header,
+    records: &[T],
+    compression: Compression,
+) -> anyhow::Result<()> {
+    let header = header.with_record_count(records.len());
+    let mut writer = NdjsonWriter::create(&header, compression).await?;
+    for record in records {
+        writer.write(record)?;
+    }
+    let written = writer.finish().await?;
+    anyhow::ensure!(
+        written == records.len(),
+        "wrote {written} of {} {} records",
+        records.len(),
+        header.dataset
+    );
+    Ok(())
+}
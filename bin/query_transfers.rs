@@ -0,0 +1,72 @@
+use clap::Parser;
+use query::{
+    cache::CacheArgs,
+    envelope::Header,
+    output::{self, Compression, Format, NdjsonWriter},
+    transfers::{self, TransferFilter, TransfersBuilder},
+};
+
+/// Export the ownership timeline of every domain from `Transfer` events.
+#[derive(Parser)]
+struct Args {
+    /// Only this domain, by id (`0x...`) or full name.
+    #[arg(long)]
+    domain: Option<String>,
+    /// Only transfers from or to this address.
+    #[arg(long)]
+    account: Option<String>,
+    /// Only transfers at or after this unix timestamp.
+    #[arg(long)]
+    since: Option<i64>,
+    /// Only transfers before this unix timestamp.
+    #[arg(long)]
+    until: Option<i64>,
+    /// json with one timeline per domain, or ndjson with one transfer per
+    /// line.
+    #[arg(long, default_value = "json")]
+    format: Format,
+    /// Compress the output with gzip or zstd, adding `.gz` or `.zst`.
+    #[arg(long, default_value = "none")]
+    compress: Compression,
+    #[command(flatten)]
+    cache: CacheArgs,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    args.cache.install()?;
+    let filter = TransferFilter {
+        domain: args.domain,
+        account: args.account,
+        since: args.since,
+        until: args.until,
+        after_block: None,
+    };
+
+    let meta = args.cache.meta().await?;
+    let block = meta.block_number;
+    let header = Header::new("transfers", &meta).with_params(serde_json::to_value(&filter)?);
+
+    match args.format {
+        Format::Json => {}
+        Format::Ndjson => {
+            let mut writer = NdjsonWriter::create(&header, args.compress).await?;
+            TransfersBuilder::query_each(&filter, block, |transfer| writer.write(&transfer))
+                .await?;
+
+            println!("transfers len:{}", writer.finish().await?);
+            return Ok(());
+        }
+        format => anyhow::bail!("{format} is not supported for transfers"),
+    }
+
+    let transfers = TransfersBuilder::query(&filter, block).await;
+
+    println!("transfers len:{}", transfers.len());
+
+    let header = header.with_record_count(transfers.len());
+    let timelines = transfers::timelines(transfers);
+
+    output::write_json(&header, &timelines, args.compress).await
+}
@@ -0,0 +1,313 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use clap::{Parser, Subcommand};
+use query::{
+    account_profile,
+    approvals::{self, ApprovalsBuilder, AuthorisationsBuilder},
+    cache::CacheArgs,
+    capacity::{self, SortBy},
+    domains::DomainsBuilder,
+    envelope::Header,
+    expiry::{self, ExpiryWindow},
+    meta::Meta,
+    output::{self, Compression, Format, NdjsonWriter},
+    prices::{self, PriceChangesBuilder, PriceSchedule},
+    query_all_at,
+    registration_events::{self, RegistrationEventsBuilder},
+    registrations::RecordsBuilder,
+    revenue::{self, Period},
+    transfers::TransfersBuilder,
+};
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Parser)]
+#[command(about = "Reports computed from the PNS subgraph")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    /// json writes the report in one document, ndjson writes one line per
+    /// row, owner, account or profile.
+    #[arg(long, global = true, default_value = "json")]
+    format: Format,
+    /// Compress the report with gzip or zstd, adding `.gz` or `.zst`.
+    #[arg(long, global = true, default_value = "none")]
+    compress: Compression,
+    #[command(flatten)]
+    cache: CacheArgs,
+}
+
+// serialized without the variant as the `params` of the report header
+#[derive(Subcommand, Serialize)]
+#[serde(untagged)]
+enum Command {
+    /// Registration and renewal costs per period and name length.
+    #[serde(rename_all = "camelCase")]
+    Revenue {
+        /// day, week or month.
+        #[arg(long, default_value = "month")]
+        period: Period,
+        /// Only events at or after this unix timestamp.
+        #[arg(long)]
+        since: Option<i64>,
+        /// Only events before this unix timestamp.
+        #[arg(long)]
+        until: Option<i64>,
+        /// Compare every cost with the price schedule and list mismatches.
+        #[arg(long)]
+        check_prices: bool,
+    },
+    /// Every price change, decoded per name length.
+    Prices,
+    /// Names expiring soon, in their grace period or expired but still owned,
+    /// grouped by owner.
+    #[serde(rename_all = "camelCase")]
+    Expiry {
+        /// Names expiring within this many days.
+        #[arg(long, default_value_t = 30)]
+        days: i64,
+        /// Length of the grace period after expiry, in days.
+        #[arg(long, default_value_t = 90)]
+        grace_days: i64,
+        /// Reference unix timestamp, defaults to now.
+        #[arg(long)]
+        now: Option<i64>,
+    },
+    /// Active operator authorisations and token approvals per account.
+    #[serde(rename_all = "camelCase")]
+    Approvals {
+        /// Flag accounts with at least this many operators.
+        #[arg(long, default_value_t = 3)]
+        max_operators: usize,
+        /// Flag operators able to manage at least this many names.
+        #[arg(long, default_value_t = 100)]
+        max_names: usize,
+    },
+    /// Subdomain capacity used per origin domain.
+    #[serde(rename_all = "camelCase")]
+    Capacity {
+        /// ratio, used or capacity, highest first.
+        #[arg(long, default_value = "ratio")]
+        sort: SortBy,
+        /// Only origins using at least this share of their capacity.
+        #[arg(long, default_value_t = 0.0)]
+        min_ratio: f64,
+    },
+    /// Owned names, registration history with costs, operator grants,
+    /// reverse name changes and the `Approval` and `SetName` events of
+    /// each account.
+    Account {
+        /// One or more addresses.
+        #[arg(required = true)]
+        addresses: Vec<String>,
+    },
+}
+
+/// Where the report goes, shared by every command.
+struct Output {
+    meta: Meta,
+    params: Value,
+    format: Format,
+    compress: Compression,
+}
+
+impl Output {
+    fn header(&self, name: &str) -> Header {
+        Header::new(name, &self.meta).with_params(self.params.clone())
+    }
+
+    /// The whole report as one JSON document.
+    async fn json<T: Serialize>(
+        &self,
+        name: &str,
+        report: &T,
+        record_count: usize,
+    ) -> anyhow::Result<()> {
+        let header = self.header(name).with_record_count(record_count);
+        output::write_json(&header, report, self.compress).await
+    }
+
+    /// One NDJSON line per row, as the dataset `name`.
+    async fn lines<T: Serialize>(
+        &self,
+        name: &str,
+        rows: impl IntoIterator<Item = T>,
+    ) -> anyhow::Result<()> {
+        let mut writer = NdjsonWriter::create(&self.header(name), self.compress).await?;
+        for row in rows {
+            writer.write(&row)?;
+        }
+        writer.finish().await?;
+        Ok(())
+    }
+
+    fn ndjson(&self) -> bool {
+        self.format == Format::Ndjson
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    if !matches!(cli.format, Format::Json | Format::Ndjson) {
+        anyhow::bail!("{} is not supported for reports", cli.format);
+    }
+    cli.cache.install()?;
+    let meta = cli.cache.meta().await?;
+    let block = meta.block_number;
+    let out = Output {
+        meta,
+        params: serde_json::to_value(&cli.command)?,
+        format: cli.format,
+        compress: cli.compress,
+    };
+
+    match cli.command {
+        Command::Revenue {
+            period,
+            since,
+            until,
+            check_prices,
+        } => {
+            let all_events = query_all_at::<RegistrationEventsBuilder>(block).await;
+            let events = all_events
+                .iter()
+                .filter(|e| since.is_none_or(|since| e.triggered_date >= since))
+                .filter(|e| until.is_none_or(|until| e.triggered_date < until))
+                .cloned()
+                .collect::<Vec<_>>();
+            let mut report = revenue::revenue(&events, period)?;
+
+            if check_prices {
+                let schedule = PriceSchedule(query_all_at::<PriceChangesBuilder>(block).await);
+                // histories need the events before the window, a renewal's
+                // duration starts at the expiry they left
+                let histories = registration_events::histories(all_events);
+                let in_window = events.iter().map(|e| e.id.as_str()).collect::<HashSet<_>>();
+                report.cost_mismatches = prices::check_costs(&histories, &schedule)
+                    .into_iter()
+                    .filter(|check| in_window.contains(check.event.as_str()))
+                    .collect();
+                println!("cost mismatches: {}", report.cost_mismatches.len());
+            }
+
+            println!("registered: {}", report.registered);
+            println!("renewed: {}", report.renewed);
+
+            if !out.ndjson() {
+                return out.json("revenue", &report, report.rows.len()).await;
+            }
+            out.lines("revenue", report.rows.iter()).await?;
+            // mismatches are not checked by default
+            if !report.cost_mismatches.is_empty() {
+                out.lines("revenue_cost_mismatches", report.cost_mismatches.iter())
+                    .await?;
+            }
+            Ok(())
+        }
+        Command::Prices => {
+            let schedule = PriceSchedule(query_all_at::<PriceChangesBuilder>(block).await);
+
+            println!("price changes len:{}", schedule.0.len());
+
+            if !out.ndjson() {
+                return out.json("prices", &schedule, schedule.0.len()).await;
+            }
+            out.lines("prices", schedule.0.iter()).await
+        }
+        Command::Expiry {
+            days,
+            grace_days,
+            now,
+        } => {
+            let window = ExpiryWindow {
+                now: now.unwrap_or_else(|| time::OffsetDateTime::now_utc().unix_timestamp()),
+                days,
+                grace_days,
+            };
+            let domains = DomainsBuilder::query(block).await?;
+            let report = expiry::expiry_report(domains, window);
+
+            println!("expiring: {}", report.expiring_num);
+            println!("in grace: {}", report.in_grace_num);
+            println!("expired: {}", report.expired_num);
+
+            if !out.ndjson() {
+                let len = report.expiring_num + report.in_grace_num + report.expired_num;
+                return out.json("expiry", &report, len).await;
+            }
+            out.lines("expiry", report.owner_rows()).await
+        }
+        Command::Approvals {
+            max_operators,
+            max_names,
+        } => {
+            let grants = query_all_at::<AuthorisationsBuilder>(block).await;
+            let approvals = query_all_at::<ApprovalsBuilder>(block).await;
+            // only a transfer of an approved name can clear an approval
+            let approved = approvals
+                .iter()
+                .map(|approval| approval.domain.clone())
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect::<Vec<_>>();
+            let transfers = TransfersBuilder::query_domains(&approved, block).await;
+            let domains = DomainsBuilder::query(block).await?;
+            let report = approvals::approvals_report(
+                grants,
+                approvals,
+                &transfers,
+                &domains,
+                max_operators,
+                max_names,
+            );
+
+            println!("flagged accounts: {}", report.flagged_accounts.len());
+            println!("flagged operators: {}", report.flagged_operators.len());
+
+            if !out.ndjson() {
+                return out.json("approvals", &report, report.accounts.len()).await;
+            }
+            out.lines("approvals", report.account_rows()).await?;
+            if !report.operators.is_empty() {
+                out.lines("approval_operators", report.operators.iter())
+                    .await?;
+            }
+            Ok(())
+        }
+        Command::Capacity { sort, min_ratio } => {
+            let records = query_all_at::<RecordsBuilder>(block)
+                .await
+                .into_iter()
+                .collect::<BTreeMap<_, _>>();
+            let report = capacity::utilisation(&records, sort, min_ratio);
+
+            println!(
+                "origins at or over capacity: {}",
+                report.iter().filter(|u| u.at_or_over).count()
+            );
+
+            if !out.ndjson() {
+                return out.json("capacity", &report, report.len()).await;
+            }
+            out.lines("capacity", report.iter()).await
+        }
+        Command::Account { addresses } => {
+            let profiles = account_profile::account_profiles(&addresses, block).await?;
+
+            for profile in profiles.iter() {
+                println!(
+                    "{}: domains {}, approval and set name events {}",
+                    profile.account,
+                    profile.domains_num,
+                    profile.account_events.len()
+                );
+            }
+
+            if !out.ndjson() {
+                return out.json("account", &profiles, profiles.len()).await;
+            }
+            out.lines("account", profiles.iter()).await
+        }
+    }
+}
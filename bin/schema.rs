@@ -0,0 +1,67 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+use query::introspection;
+
+#[derive(Parser)]
+#[command(about = "Pull or check the PNS subgraph schema")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Introspect the live subgraph and write its SDL.
+    Pull {
+        #[arg(long, default_value = "schema.gql")]
+        output: PathBuf,
+    },
+    /// Diff the live subgraph against the checked-in SDL.
+    Check {
+        #[arg(long, default_value = "schema.gql")]
+        schema: PathBuf,
+    },
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let live = introspection::introspect().await?;
+
+    match cli.command {
+        Command::Pull { output } => {
+            tokio::fs::write(&output, live.to_sdl()).await?;
+            println!("wrote {}", output.display());
+        }
+        Command::Check { schema } => {
+            let local = tokio::fs::read_to_string(&schema).await?;
+            let local = introspection::parse_shapes(&local)?;
+            let queried = introspection::queried_types(&local)?;
+            let drift = introspection::diff(&local, &live.shapes());
+
+            for d in drift.iter() {
+                if d.is_queried(&queried) {
+                    println!("{d} (queried)");
+                } else {
+                    println!("{d}");
+                }
+            }
+
+            let breaking = drift
+                .iter()
+                .filter(|d| d.is_breaking() && d.is_queried(&queried))
+                .count();
+            println!("drift: {}, breaking queried: {breaking}", drift.len());
+
+            if breaking > 0 {
+                anyhow::bail!(
+                    "{} no longer matches the live subgraph, run `schema pull`",
+                    schema.display()
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,323 @@
+/*!
+```
+query QueryAccountEvents($skip: Int = 10, $block: Block_height, $filter: AccountEvent_filter) {
+  accountEvents(
+    skip: $skip
+    first: 1000
+    block: $block
+    where: $filter
+    orderBy: blockNumber
+    orderDirection: asc
+  ) {
+    ... on Approval {
+      ...EventFields
+      operator {
+        id
+      }
+      tokens {
+        id
+        name
+        createdAt
+      }
+    }
+    ... on SetName {
+      ...EventFields
+      tokenId {
+        id
+        name
+        createdAt
+      }
+    }
+  }
+}
+
+fragment EventFields on AccountEvent {
+  id
+  blockNumber
+  triggeredDate
+  transactionID
+  account {
+    id
+  }
+}
+```
+*/
+#[cynic::schema_for_derives(file = r#"schema.gql"#, module = "schema")]
+mod queries {
+    use crate::{
+        fragments::{AccountFields, BigInt, BlockHeight, Bytes, DomainFields},
+        schema,
+    };
+
+    #[derive(cynic::QueryVariables, Debug)]
+    pub struct QueryAccountEventsVariables {
+        pub skip: Option<i32>,
+        pub block: Option<BlockHeight>,
+        pub filter: Option<AccountEventFilter>,
+    }
+
+    #[derive(cynic::InputObject, Debug, Default, Clone)]
+    #[cynic(graphql_type = "AccountEvent_filter")]
+    pub struct AccountEventFilter {
+        #[cynic(skip_serializing_if = "Option::is_none")]
+        pub account: Option<String>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    #[cynic(graphql_type = "Query", variables = "QueryAccountEventsVariables")]
+    pub struct QueryAccountEvents {
+        #[arguments(skip: $skip, first: 1000, block: $block, where: $filter, orderBy: blockNumber, orderDirection: asc)]
+        pub account_events: Vec<AccountEvent>,
+    }
+
+    #[derive(cynic::InlineFragments, Debug)]
+    pub enum AccountEvent {
+        Approval(Approval),
+        SetName(SetName),
+        #[cynic(fallback)]
+        Unknown,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    pub struct Approval {
+        pub id: cynic::Id,
+        pub block_number: i32,
+        pub triggered_date: BigInt,
+        #[cynic(rename = "transactionID")]
+        pub transaction_id: Bytes,
+        pub account: AccountFields,
+        pub operator: AccountFields,
+        pub tokens: DomainFields,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    pub struct SetName {
+        pub id: cynic::Id,
+        pub block_number: i32,
+        pub triggered_date: BigInt,
+        #[cynic(rename = "transactionID")]
+        pub transaction_id: Bytes,
+        pub account: AccountFields,
+        pub token_id: DomainFields,
+    }
+}
+
+use std::collections::BTreeSet;
+
+use serde::Serialize;
+
+use crate::{
+    approvals::{AuthorisationsBuilder, OperatorGrant},
+    domains::{DomainRecord, DomainsBuilder},
+    fragments::BlockHeight,
+    query_pages,
+    registration_events::{self, RegistrationEventsBuilder, RegistrationHistory},
+    revenue::{sum_wei, Units},
+    HandleId, IsFull, ACCOUNT_ID_LEN, DOMAIN_ID_LEN,
+};
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(tag = "type")]
+pub enum AccountEventKind {
+    Approval {
+        operator: String,
+        domain: String,
+        name: Option<String>,
+    },
+    SetName {
+        domain: String,
+        name: Option<String>,
+    },
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountEventRecord {
+    pub id: String,
+    pub account: String,
+    pub block_number: i32,
+    pub triggered_date: i64,
+    pub transaction_id: String,
+    #[serde(flatten)]
+    pub event: AccountEventKind,
+}
+
+/// A `SetName` of the account with the primary name it replaced.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ReverseNameChange {
+    pub domain: String,
+    pub name: Option<String>,
+    pub previous_name: Option<String>,
+    pub block_number: i32,
+    pub triggered_date: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountProfile {
+    pub account: String,
+    pub domains_num: usize,
+    pub domains: Vec<DomainRecord>,
+    /// Histories of the names this account registered or renewed. Events
+    /// sent by other accounts for the same names are left out, but still
+    /// count for the expiry and capacity each event replaced.
+    pub registrations: Vec<RegistrationHistory>,
+    pub total_cost_wei: String,
+    pub total_cost: String,
+    /// Operator changes where the account is the owner or the operator.
+    pub operator_grants: Vec<OperatorGrant>,
+    pub reverse_names: Vec<ReverseNameChange>,
+    /// `Approval` and `SetName` events sent by the account, not a full
+    /// event log.
+    pub account_events: Vec<AccountEventRecord>,
+}
+
+pub struct AccountEventsBuilder;
+
+impl AccountEventsBuilder {
+    fn build_query(
+        offset: i32,
+        block: i32,
+        filter: queries::AccountEventFilter,
+    ) -> cynic::Operation<queries::QueryAccountEvents, queries::QueryAccountEventsVariables> {
+        <queries::QueryAccountEvents as cynic::QueryBuilder>::build(
+            queries::QueryAccountEventsVariables {
+                skip: Some(offset),
+                block: Some(BlockHeight::number(block)),
+                filter: Some(filter),
+            },
+        )
+    }
+
+    /// `Approval` and `SetName` events sent by `account`, in block order.
+    pub async fn query(account: &str, block: i32) -> Vec<AccountEventRecord> {
+        let filter = queries::AccountEventFilter {
+            account: Some(account.to_lowercase()),
+        };
+
+        query_pages(|offset| Self::build_query(offset, block, filter.clone())).await
+    }
+}
+
+impl IsFull for queries::QueryAccountEvents {
+    type Item = AccountEventRecord;
+
+    fn len(&self) -> usize {
+        self.account_events.len()
+    }
+
+    fn into_iter(self) -> impl IntoIterator<Item = Self::Item> {
+        IntoIterator::into_iter(self.account_events).filter_map(|event| {
+            use queries::AccountEvent as E;
+
+            let (id, block_number, triggered_date, transaction_id, account, event) = match event {
+                E::Approval(e) => (
+                    e.id,
+                    e.block_number,
+                    e.triggered_date,
+                    e.transaction_id,
+                    e.account,
+                    AccountEventKind::Approval {
+                        operator: e.operator.id.0.handle_id::<ACCOUNT_ID_LEN>(),
+                        domain: e.tokens.id.0.handle_id::<DOMAIN_ID_LEN>(),
+                        name: e.tokens.name,
+                    },
+                ),
+                E::SetName(e) => (
+                    e.id,
+                    e.block_number,
+                    e.triggered_date,
+                    e.transaction_id,
+                    e.account,
+                    AccountEventKind::SetName {
+                        domain: e.token_id.id.0.handle_id::<DOMAIN_ID_LEN>(),
+                        name: e.token_id.name,
+                    },
+                ),
+                E::Unknown => return None,
+            };
+
+            Some(AccountEventRecord {
+                id: id.inner().to_string(),
+                account: account.id.0.handle_id::<ACCOUNT_ID_LEN>(),
+                block_number,
+                triggered_date: triggered_date.0.parse().unwrap(),
+                transaction_id: transaction_id.0,
+                event,
+            })
+        })
+    }
+}
+
+/// Everything the subgraph knows about `account` at `block`.
+pub async fn account_profile(account: &str, block: i32) -> anyhow::Result<AccountProfile> {
+    let account = account.to_lowercase();
+
+    let domains = DomainsBuilder::query_owner(&account, block).await?;
+    let registration_events = RegistrationEventsBuilder::query_registrant(&account, block).await;
+    let operator_grants = AuthorisationsBuilder::query_account(&account, block).await;
+    let events = AccountEventsBuilder::query(&account, block).await;
+
+    let total_cost = sum_wei(registration_events.iter().filter_map(|e| e.cost.as_deref()))?;
+
+    // histories need every event of a name to know what each one replaced
+    let names = registration_events
+        .iter()
+        .map(|e| e.domain.clone())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let mut registrations = registration_events::histories(
+        RegistrationEventsBuilder::query_domains(&names, block).await,
+    );
+    // the account as the subgraph spells it
+    let registrants = registration_events
+        .iter()
+        .map(|e| e.registrant.as_str())
+        .collect::<BTreeSet<_>>();
+    for history in registrations.iter_mut() {
+        history
+            .events
+            .retain(|e| registrants.contains(e.registrant.as_str()));
+    }
+
+    let mut previous_name = None;
+    let reverse_names = events
+        .iter()
+        .filter_map(|e| match &e.event {
+            AccountEventKind::SetName { domain, name } => Some(ReverseNameChange {
+                domain: domain.clone(),
+                name: name.clone(),
+                previous_name: std::mem::replace(&mut previous_name, name.clone()),
+                block_number: e.block_number,
+                triggered_date: e.triggered_date,
+            }),
+            AccountEventKind::Approval { .. } => None,
+        })
+        .collect();
+
+    Ok(AccountProfile {
+        account,
+        domains_num: domains.len(),
+        domains,
+        registrations,
+        total_cost_wei: total_cost.to_string(),
+        total_cost: Units(total_cost).to_string(),
+        operator_grants,
+        reverse_names,
+        account_events: events,
+    })
+}
+
+/// [`account_profile`] for each of `accounts`, in the given order.
+pub async fn account_profiles(
+    accounts: &[String],
+    block: i32,
+) -> anyhow::Result<Vec<AccountProfile>> {
+    let mut res = Vec::with_capacity(accounts.len());
+    for account in accounts {
+        res.push(account_profile(account, block).await?);
+    }
+    Ok(res)
+}
@@ -1,21 +1,23 @@
 /*!
 ```
-query QueryAccounts($skip: Int = 10) {
-  accounts(first: 1000, skip: $skip) {
+query QueryAccounts($skip: Int = 10, $block: Block_height) {
+  accounts(first: 1000, skip: $skip, block: $block) {
     id
     domains(
       first: 1000
       where: {parent: "0x3fce7d1364a893e213bc4212792b517ffc88f5b13b86c8ef9c8d390c3a1370ce"}
     ) {
+      id
       name
       createdAt
     }
   }
 }
 
-query QueryDomains($skip: Int = 10, $id: ID = "") {
-  account(id: $id) {
+query QueryDomains($skip: Int = 10, $id: ID = "", $block: Block_height) {
+  account(id: $id, block: $block) {
     domains(first: 1000, skip: $skip) {
+      id
       name
       createdAt
     }
@@ -28,30 +30,35 @@ const MAX_TIMESTAMP: i32 = 1667908800;
 
 #[cynic::schema_for_derives(file = r#"schema.gql"#, module = "schema")]
 mod queries {
-    use super::schema;
+    use crate::{
+        fragments::{BlockHeight, Bytes, DomainFields},
+        schema,
+    };
 
     #[derive(cynic::QueryVariables, Debug)]
     pub struct QueryAccountsVariables {
         pub skip: Option<i32>,
+        pub block: Option<BlockHeight>,
     }
 
     #[derive(cynic::QueryVariables, Debug)]
     pub struct QueryDomainsVariables {
         pub id: cynic::Id,
         pub skip: Option<i32>,
+        pub block: Option<BlockHeight>,
     }
 
     #[derive(cynic::QueryFragment, Debug)]
     #[cynic(graphql_type = "Query", variables = "QueryAccountsVariables")]
     pub struct QueryAccounts {
-        #[arguments(first: 1000, skip: $skip)]
+        #[arguments(first: 1000, skip: $skip, block: $block)]
         pub accounts: Vec<Account>,
     }
 
     #[derive(cynic::QueryFragment, Debug)]
     #[cynic(graphql_type = "Query", variables = "QueryDomainsVariables")]
     pub struct QueryDomains {
-        #[arguments(id: $id)]
+        #[arguments(id: $id, block: $block)]
         pub account: Option<Account2>,
     }
 
@@ -59,45 +66,35 @@ mod queries {
     pub struct Account {
         pub id: Bytes,
         #[arguments(first: 1000, where: { parent: "0x3fce7d1364a893e213bc4212792b517ffc88f5b13b86c8ef9c8d390c3a1370ce" })]
-        pub domains: Vec<Domain>,
+        pub domains: Vec<DomainFields>,
     }
 
     #[derive(cynic::QueryFragment, Debug)]
     #[cynic(graphql_type = "Account", variables = "QueryDomainsVariables")]
     pub struct Account2 {
         #[arguments(first: 1000, skip: $skip)]
-        pub domains: Vec<Domain>,
-    }
-
-    #[derive(cynic::QueryFragment, Debug)]
-    pub struct Domain {
-        pub name: Option<String>,
-        pub created_at: BigInt,
+        pub domains: Vec<DomainFields>,
     }
-
-    #[derive(cynic::Scalar, Debug, Clone)]
-    pub struct BigInt(pub String);
-
-    #[derive(cynic::Scalar, Debug, Clone)]
-    pub struct Bytes(pub String);
-}
-
-#[allow(non_snake_case, non_camel_case_types)]
-mod schema {
-    cynic::use_schema!(r#"schema.gql"#);
 }
 
+use arrow_array::RecordBatch;
+use arrow_schema::Schema;
 use futures_util::{stream, Stream, StreamExt};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 
-use crate::{run_graphql, HandleId, IsFull, IsFullAsync, ACCOUNT_ID_LEN, FIRST, OFFSET};
+use crate::{
+    columnar::{self, AccountId, Columnar, Text},
+    fragments::BlockHeight,
+    output::Tabular,
+    run_graphql, HandleId, IsFull, IsFullAsync, ACCOUNT_ID_LEN, FIRST, OFFSET,
+};
 
 #[derive(Debug, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct AllAccounts {
     pub accounts_num: usize,
-    pub accounts: HashSet<Account>,
+    pub accounts: BTreeSet<Account>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Eq, Clone)]
@@ -105,9 +102,49 @@ pub struct AllAccounts {
 pub struct Account {
     id: String,
     domains_num: usize,
-    pub domains: HashSet<String>,
+    pub domains: BTreeSet<String>,
+}
+
+/// One account/domain pair. Accounts without domains get a single row with
+/// an empty domain so they are not lost.
+#[derive(Serialize)]
+pub struct AccountDomainRow<'a> {
+    pub account: &'a str,
+    pub domain: Option<&'a str>,
 }
 
+impl Tabular for AllAccounts {
+    type Row<'a> = AccountDomainRow<'a>;
+
+    fn rows(&self) -> impl Iterator<Item = Self::Row<'_>> {
+        self.accounts.iter().flat_map(|account| {
+            let domains = account.domains.iter().map(|domain| Some(domain.as_str()));
+            let empty = account.domains.is_empty().then_some(None);
+
+            domains.chain(empty).map(move |domain| AccountDomainRow {
+                account: &account.id,
+                domain,
+            })
+        })
+    }
+}
+
+/// Same rows as the CSV, domains are names rather than ids.
+impl Columnar for AllAccounts {
+    fn schema() -> Schema {
+        columnar::schema::<AccountDomainRow>()
+    }
+
+    fn to_batch(&self) -> anyhow::Result<RecordBatch> {
+        columnar::to_batch(&self.rows().collect::<Vec<_>>())
+    }
+}
+
+crate::columnar_row!(AccountDomainRow<'_> {
+    account: AccountId,
+    domain: Text,
+});
+
 impl PartialEq for Account {
     fn eq(&self, other: &Self) -> bool {
         self.id == other.id
@@ -120,46 +157,89 @@ impl core::hash::Hash for Account {
     }
 }
 
+impl PartialOrd for Account {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// By id, so exports list accounts in a stable order.
+impl Ord for Account {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.id.cmp(&other.id)
+    }
+}
+
 pub struct QueryDomainsBuilder;
 
 impl QueryDomainsBuilder {
     fn build_query(
         offset: i32,
+        block: i32,
         id: cynic::Id,
     ) -> cynic::Operation<queries::QueryDomains, queries::QueryDomainsVariables> {
         <queries::QueryDomains as cynic::QueryBuilder>::build(queries::QueryDomainsVariables {
             skip: Some(offset),
+            block: Some(BlockHeight::number(block)),
             id,
         })
     }
 
-    pub async fn query() -> AllAccounts {
+    /// Every account at `block`.
+    pub async fn query(block: i32) -> AllAccounts {
+        let mut res = BTreeSet::default();
+
+        Self::query_each(block, |account| {
+            res.insert(account);
+            Ok(())
+        })
+        .await
+        .expect("collecting accounts does not fail");
+
+        AllAccounts {
+            accounts_num: res.len(),
+            accounts: res,
+        }
+    }
+
+    /// Hand every account at `block` to `on_account` as its page arrives,
+    /// each one once.
+    pub async fn query_each(
+        block: i32,
+        mut on_account: impl FnMut(Account) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
         let mut is_full = true;
         let mut offset = 0;
-        let mut res = HashSet::default();
+        let mut seen = HashSet::new();
 
         while is_full {
             let data = run_graphql(<queries::QueryAccounts as cynic::QueryBuilder>::build(
-                queries::QueryAccountsVariables { skip: Some(offset) },
+                queries::QueryAccountsVariables {
+                    skip: Some(offset),
+                    block: Some(BlockHeight::number(block)),
+                },
             ))
             .await
             .data
             .unwrap();
-            offset += OFFSET;
+            offset += 1000;
             is_full = data.is_full();
 
-            res.extend(data.into_stream().await.collect::<HashSet<_>>().await);
-        }
+            let mut stream = data.into_stream(block).await.boxed();
 
-        AllAccounts {
-            accounts_num: res.len(),
-            accounts: res,
+            while let Some(account) = stream.next().await {
+                if seen.insert(account.id.clone()) {
+                    on_account(account)?;
+                }
+            }
         }
+
+        Ok(())
     }
 }
 
 impl IsFull for queries::QueryDomains {
-    type Item = HashSet<String>;
+    type Item = BTreeSet<String>;
 
     fn len(&self) -> usize {
         self.account
@@ -179,7 +259,7 @@ impl IsFull for queries::QueryDomains {
                         }
                         d.name
                     })
-                    .collect::<HashSet<_>>()
+                    .collect::<BTreeSet<_>>()
             })
             .into_iter()
     }
@@ -192,8 +272,8 @@ impl IsFullAsync for queries::QueryAccounts {
         self.accounts.len()
     }
 
-    async fn into_stream(self) -> impl Stream<Item = Self::Item> {
-        stream::iter(self.accounts.into_iter()).filter_map(|account| async move {
+    async fn into_stream(self, block: i32) -> impl Stream<Item = Self::Item> {
+        stream::iter(self.accounts.into_iter()).filter_map(move |account| async move {
             let domains_len = account.domains.len();
             let mut full = None;
             if domains_len == FIRST {
@@ -204,6 +284,7 @@ impl IsFullAsync for queries::QueryAccounts {
                 while is_full {
                     let data = run_graphql(QueryDomainsBuilder::build_query(
                         offset,
+                        block,
                         cynic::Id::new(&account.id.0),
                     ))
                     .await
@@ -227,7 +308,7 @@ impl IsFullAsync for queries::QueryAccounts {
                     }
                     d.name
                 })
-                .collect::<HashSet<_>>();
+                .collect::<BTreeSet<_>>();
             if let Some(full) = full {
                 full.into_iter().for_each(|set| domains.extend(set));
             }
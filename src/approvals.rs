@@ -0,0 +1,511 @@
+/*!
+```
+query QueryAuthorisations($skip: Int = 10, $block: Block_height, $filter: AuthorisationChanged_filter) {
+  authorisationChangeds(
+    skip: $skip
+    first: 1000
+    block: $block
+    where: $filter
+    orderBy: blockNumber
+    orderDirection: asc
+  ) {
+    id
+    blockNumber
+    triggeredDate
+    isAuthorized
+    owner {
+      id
+    }
+    target {
+      id
+    }
+  }
+}
+
+query QueryApprovals($skip: Int = 10, $block: Block_height) {
+  approvals(skip: $skip, first: 1000, block: $block, orderBy: blockNumber, orderDirection: asc) {
+    id
+    blockNumber
+    triggeredDate
+    account {
+      id
+    }
+    operator {
+      id
+    }
+    tokens {
+      id
+      name
+      createdAt
+    }
+  }
+}
+```
+*/
+#[cynic::schema_for_derives(file = r#"schema.gql"#, module = "schema")]
+mod queries {
+    use crate::{
+        fragments::{AccountFields, BigInt, BlockHeight, DomainFields},
+        schema,
+    };
+
+    #[derive(cynic::QueryVariables, Debug)]
+    pub struct PageVariables {
+        pub skip: Option<i32>,
+        pub block: Option<BlockHeight>,
+    }
+
+    #[derive(cynic::QueryVariables, Debug)]
+    pub struct QueryAuthorisationsVariables {
+        pub skip: Option<i32>,
+        pub block: Option<BlockHeight>,
+        pub filter: Option<AuthorisationChangedFilter>,
+    }
+
+    #[derive(cynic::InputObject, Debug, Default, Clone)]
+    #[cynic(graphql_type = "AuthorisationChanged_filter")]
+    pub struct AuthorisationChangedFilter {
+        #[cynic(skip_serializing_if = "Option::is_none")]
+        pub owner: Option<String>,
+        #[cynic(skip_serializing_if = "Option::is_none")]
+        pub target: Option<String>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    #[cynic(graphql_type = "Query", variables = "QueryAuthorisationsVariables")]
+    pub struct QueryAuthorisations {
+        #[arguments(skip: $skip, first: 1000, block: $block, where: $filter, orderBy: blockNumber, orderDirection: asc)]
+        pub authorisation_changeds: Vec<AuthorisationChanged>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    #[cynic(graphql_type = "Query", variables = "PageVariables")]
+    pub struct QueryApprovals {
+        #[arguments(skip: $skip, first: 1000, block: $block, orderBy: blockNumber, orderDirection: asc)]
+        pub approvals: Vec<Approval>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    pub struct AuthorisationChanged {
+        pub id: cynic::Id,
+        pub block_number: i32,
+        pub triggered_date: BigInt,
+        pub is_authorized: bool,
+        pub owner: AccountFields,
+        pub target: AccountFields,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    pub struct Approval {
+        pub id: cynic::Id,
+        pub block_number: i32,
+        pub triggered_date: BigInt,
+        pub account: AccountFields,
+        pub operator: AccountFields,
+        pub tokens: DomainFields,
+    }
+}
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use serde::Serialize;
+
+use crate::{
+    domains::DomainRecord, event_order, fragments::BlockHeight, query_pages,
+    transfers::TransferRecord, BuildQuery, HandleId, IsFull, ACCOUNT_ID_LEN, DOMAIN_ID_LEN,
+};
+
+const ZERO_ADDRESS: &str = "0x0000000000000000000000000000000000000000";
+
+/// `owner` let `operator` manage all of its names, or revoked it.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OperatorGrant {
+    pub id: String,
+    pub owner: String,
+    pub operator: String,
+    pub is_authorized: bool,
+    pub block_number: i32,
+    pub triggered_date: i64,
+}
+
+/// `account` approved `operator` for one name.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenApproval {
+    pub id: String,
+    pub account: String,
+    pub operator: String,
+    pub domain: String,
+    pub name: Option<String>,
+    pub block_number: i32,
+    pub triggered_date: i64,
+}
+
+pub struct AuthorisationsBuilder;
+pub struct ApprovalsBuilder;
+
+impl AuthorisationsBuilder {
+    fn build_query_filtered(
+        offset: i32,
+        block: i32,
+        filter: queries::AuthorisationChangedFilter,
+    ) -> cynic::Operation<queries::QueryAuthorisations, queries::QueryAuthorisationsVariables> {
+        <queries::QueryAuthorisations as cynic::QueryBuilder>::build(
+            queries::QueryAuthorisationsVariables {
+                skip: Some(offset),
+                block: Some(BlockHeight::number(block)),
+                filter: Some(filter),
+            },
+        )
+    }
+
+    /// Operator changes where `account` is either the owner or the operator,
+    /// in block order.
+    pub async fn query_account(account: &str, block: i32) -> Vec<OperatorGrant> {
+        let account = account.to_lowercase();
+        let filters = [
+            queries::AuthorisationChangedFilter {
+                owner: Some(account.clone()),
+                ..Default::default()
+            },
+            queries::AuthorisationChangedFilter {
+                target: Some(account),
+                ..Default::default()
+            },
+        ];
+
+        let mut res = BTreeMap::new();
+        for filter in filters {
+            let grants =
+                query_pages(|offset| Self::build_query_filtered(offset, block, filter.clone()))
+                    .await;
+            // an account operating for itself matches both sides
+            res.extend(grants.into_iter().map(|g| (g.id.clone(), g)));
+        }
+
+        let mut res = res.into_values().collect::<Vec<_>>();
+        res.sort_by_key(|grant| grant.block_number);
+        res
+    }
+}
+
+impl BuildQuery for AuthorisationsBuilder {
+    type Vars = queries::QueryAuthorisationsVariables;
+
+    type ResponseData = queries::QueryAuthorisations;
+
+    fn build_query(offset: i32, block: i32) -> cynic::Operation<Self::ResponseData, Self::Vars> {
+        Self::build_query_filtered(offset, block, Default::default())
+    }
+}
+
+impl BuildQuery for ApprovalsBuilder {
+    type Vars = queries::PageVariables;
+
+    type ResponseData = queries::QueryApprovals;
+
+    fn build_query(offset: i32, block: i32) -> cynic::Operation<Self::ResponseData, Self::Vars> {
+        <queries::QueryApprovals as cynic::QueryBuilder>::build(page(offset, block))
+    }
+}
+
+fn page(offset: i32, block: i32) -> queries::PageVariables {
+    queries::PageVariables {
+        skip: Some(offset),
+        block: Some(BlockHeight::number(block)),
+    }
+}
+
+impl IsFull for queries::QueryAuthorisations {
+    type Item = OperatorGrant;
+
+    fn len(&self) -> usize {
+        self.authorisation_changeds.len()
+    }
+
+    fn into_iter(self) -> impl IntoIterator<Item = Self::Item> {
+        IntoIterator::into_iter(self.authorisation_changeds).map(|change| OperatorGrant {
+            id: change.id.inner().to_string(),
+            owner: change.owner.id.0.handle_id::<ACCOUNT_ID_LEN>(),
+            operator: change.target.id.0.handle_id::<ACCOUNT_ID_LEN>(),
+            is_authorized: change.is_authorized,
+            block_number: change.block_number,
+            triggered_date: change.triggered_date.0.parse().unwrap(),
+        })
+    }
+}
+
+impl IsFull for queries::QueryApprovals {
+    type Item = TokenApproval;
+
+    fn len(&self) -> usize {
+        self.approvals.len()
+    }
+
+    fn into_iter(self) -> impl IntoIterator<Item = Self::Item> {
+        IntoIterator::into_iter(self.approvals).map(|approval| TokenApproval {
+            id: approval.id.inner().to_string(),
+            account: approval.account.id.0.handle_id::<ACCOUNT_ID_LEN>(),
+            operator: approval.operator.id.0.handle_id::<ACCOUNT_ID_LEN>(),
+            domain: approval.tokens.id.0.handle_id::<DOMAIN_ID_LEN>(),
+            name: approval.tokens.name,
+            block_number: approval.block_number,
+            triggered_date: approval.triggered_date.0.parse().unwrap(),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountAuthorizations {
+    pub operators: Vec<OperatorGrant>,
+    pub token_approvals: Vec<TokenApproval>,
+}
+
+/// One account of [`ApprovalsReport::accounts`], with the account moved in.
+#[derive(Debug, Serialize)]
+pub struct AccountAuthorizationsRow<'a> {
+    pub account: &'a str,
+    #[serde(flatten)]
+    pub authorizations: &'a AccountAuthorizations,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperatorExposure {
+    pub operator: String,
+    /// Accounts that authorised this operator for all their names.
+    pub accounts: usize,
+    /// Single names approved to this operator.
+    pub tokens: usize,
+    /// Names this operator can currently manage, either way.
+    pub names: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApprovalsReport {
+    pub accounts: BTreeMap<String, AccountAuthorizations>,
+    pub operators: Vec<OperatorExposure>,
+    /// Accounts with at least `max_operators` active operators.
+    pub flagged_accounts: Vec<String>,
+    /// Operators able to manage at least `max_names` names.
+    pub flagged_operators: Vec<String>,
+}
+
+impl ApprovalsReport {
+    /// One row per account, in account order.
+    pub fn account_rows(&self) -> impl Iterator<Item = AccountAuthorizationsRow<'_>> {
+        self.accounts
+            .iter()
+            .map(|(account, authorizations)| AccountAuthorizationsRow {
+                account,
+                authorizations,
+            })
+    }
+}
+
+/// Fold the event history into the authorisations active now.
+///
+/// Later `AuthorisationChanged` events for the same owner and operator
+/// replace earlier ones. A token approval is replaced by the next approval of
+/// the same name, cleared by approving the zero address and, as for any
+/// ERC-721, cleared when the name is transferred.
+pub fn approvals_report(
+    grants: Vec<OperatorGrant>,
+    approvals: Vec<TokenApproval>,
+    transfers: &[TransferRecord],
+    domains: &[DomainRecord],
+    max_operators: usize,
+    max_names: usize,
+) -> ApprovalsReport {
+    let mut operators = BTreeMap::<_, OperatorGrant>::new();
+    for grant in grants {
+        let key = (grant.owner.clone(), grant.operator.clone());
+        match operators.get(&key) {
+            Some(last)
+                if event_order(last.block_number, &last.id)
+                    > event_order(grant.block_number, &grant.id) => {}
+            _ => {
+                operators.insert(key, grant);
+            }
+        }
+    }
+
+    let mut tokens = BTreeMap::<_, TokenApproval>::new();
+    for approval in approvals {
+        match tokens.get(&approval.domain) {
+            Some(last)
+                if event_order(last.block_number, &last.id)
+                    > event_order(approval.block_number, &approval.id) => {}
+            _ => {
+                tokens.insert(approval.domain.clone(), approval);
+            }
+        }
+    }
+
+    let last_transfer = transfers.iter().fold(HashMap::new(), |mut map, t| {
+        let order = event_order(t.block_number, &t.id);
+        let last = map.entry(t.domain.as_str()).or_insert(order);
+        *last = (*last).max(order);
+        map
+    });
+    tokens.retain(|domain, approval| {
+        approval.operator != ZERO_ADDRESS
+            && last_transfer
+                .get(domain.as_str())
+                .is_none_or(|last| *last < event_order(approval.block_number, &approval.id))
+    });
+
+    let mut owned = HashMap::<&str, Vec<&str>>::new();
+    for d in domains {
+        if let Some(owner) = d.owner.as_deref() {
+            owned.entry(owner).or_default().push(d.id.as_str());
+        }
+    }
+
+    let mut accounts = BTreeMap::<String, AccountAuthorizations>::new();
+    // a name reachable through both a grant and a token approval counts once
+    let mut exposure = BTreeMap::<String, (usize, usize, BTreeSet<String>)>::new();
+
+    for grant in operators.into_values().filter(|g| g.is_authorized) {
+        let (accounts_num, _, names) = exposure.entry(grant.operator.clone()).or_default();
+        *accounts_num += 1;
+        names.extend(
+            owned
+                .get(grant.owner.as_str())
+                .into_iter()
+                .flatten()
+                .map(|id| id.to_string()),
+        );
+
+        accounts
+            .entry(grant.owner.clone())
+            .or_default()
+            .operators
+            .push(grant);
+    }
+
+    for approval in tokens.into_values() {
+        let (_, tokens_num, names) = exposure.entry(approval.operator.clone()).or_default();
+        *tokens_num += 1;
+        names.insert(approval.domain.clone());
+
+        accounts
+            .entry(approval.account.clone())
+            .or_default()
+            .token_approvals
+            .push(approval);
+    }
+
+    let flagged_accounts = accounts
+        .iter()
+        .filter(|(_, a)| a.operators.len() >= max_operators)
+        .map(|(account, _)| account.clone())
+        .collect();
+
+    let operators = exposure
+        .into_iter()
+        .map(|(operator, (accounts, tokens, names))| OperatorExposure {
+            operator,
+            accounts,
+            tokens,
+            names: names.len(),
+        })
+        .collect::<Vec<_>>();
+
+    let flagged_operators = operators
+        .iter()
+        .filter(|o| o.names >= max_names)
+        .map(|o| o.operator.clone())
+        .collect();
+
+    ApprovalsReport {
+        accounts,
+        operators,
+        flagged_accounts,
+        flagged_operators,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OWNER: &str = "0x0000000000000000000000000000000000000001";
+    const OPERATOR: &str = "0x0000000000000000000000000000000000000002";
+    const DOMAIN: &str = "0x0101010101010101010101010101010101010101010101010101010101010101";
+
+    fn approval(id: &str, block_number: i32) -> TokenApproval {
+        TokenApproval {
+            id: id.to_string(),
+            account: OWNER.to_string(),
+            operator: OPERATOR.to_string(),
+            domain: DOMAIN.to_string(),
+            name: None,
+            block_number,
+            triggered_date: 0,
+        }
+    }
+
+    fn transfer(id: &str, block_number: i32) -> TransferRecord {
+        TransferRecord {
+            id: id.to_string(),
+            domain: DOMAIN.to_string(),
+            name: None,
+            from: Some(OWNER.to_string()),
+            to: OPERATOR.to_string(),
+            block_number,
+            triggered_date: 0,
+            transaction_id: String::new(),
+        }
+    }
+
+    fn approved(approvals: Vec<TokenApproval>, transfers: &[TransferRecord]) -> usize {
+        approvals_report(
+            Vec::new(),
+            approvals,
+            transfers,
+            &[],
+            usize::MAX,
+            usize::MAX,
+        )
+        .accounts
+        .values()
+        .map(|a| a.token_approvals.len())
+        .sum()
+    }
+
+    #[test]
+    fn transfer_in_the_same_block_clears_an_earlier_approval() {
+        assert_eq!(
+            approved(vec![approval("0xa-3", 100)], &[transfer("0xa-7", 100)]),
+            0
+        );
+        assert_eq!(
+            approved(vec![approval("0xa-7", 100)], &[transfer("0xa-3", 100)]),
+            1
+        );
+        assert_eq!(
+            approved(vec![approval("0xa-3", 101)], &[transfer("0xa-7", 100)]),
+            1
+        );
+    }
+
+    #[test]
+    fn later_approval_in_the_same_block_wins() {
+        let revoke = |id| TokenApproval {
+            operator: ZERO_ADDRESS.to_string(),
+            ..approval(id, 100)
+        };
+        assert_eq!(
+            approved(vec![revoke("0xa-12"), approval("0xa-3", 100)], &[]),
+            0
+        );
+        assert_eq!(
+            approved(vec![approval("0xa-12", 100), revoke("0xa-3")], &[]),
+            1
+        );
+    }
+}
@@ -0,0 +1,236 @@
+//! On-disk cache of raw subgraph responses, consulted by [`crate::run_graphql`].
+//!
+//! Only queries pinned to a block number are cached, keyed by the SHA-256 of
+//! the subgraph deployment, query text and variables, so a redeployment never
+//! answers from another deployment's responses. Blocks within
+//! `--confirmations` of the head may still be reorganised and are not cached,
+//! and nothing is until [`crate::meta::query_meta`] has reported the
+//! deployment.
+//! Responses carrying errors are never stored. Once the cache grows past its
+//! size limit the oldest responses are evicted first.
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        OnceLock, RwLock,
+    },
+    time::{Duration, SystemTime},
+};
+
+use serde::Serialize;
+
+use crate::{
+    meta::{self, Meta},
+    output,
+};
+
+static CACHE: OnceLock<Cache> = OnceLock::new();
+static HEAD: RwLock<Option<Head>> = RwLock::new(None);
+
+/// Latest block seen by [`crate::meta::query_meta`].
+struct Head {
+    deployment: String,
+    block_number: i32,
+}
+
+/// Cache options shared by the exporters, flattened into their arguments.
+#[derive(clap::Args, Debug)]
+pub struct CacheArgs {
+    /// Always query the subgraph, without reading or writing the response
+    /// cache.
+    #[arg(long, global = true)]
+    pub no_cache: bool,
+    /// Directory of the response cache.
+    #[arg(long, global = true, default_value = ".graphql-cache")]
+    pub cache_dir: PathBuf,
+    /// Responses older than this many seconds are fetched again.
+    #[arg(long, global = true, default_value_t = 7 * 24 * 60 * 60)]
+    pub cache_ttl: u64,
+    /// Evict the oldest responses once the cache exceeds this many MiB.
+    #[arg(long, global = true, default_value_t = 1024)]
+    pub cache_max_mib: u64,
+    /// Export at this block instead of the subgraph's latest one.
+    #[arg(long, global = true)]
+    pub block: Option<i32>,
+    /// Blocks this close to the head can still be reorganised and are never
+    /// cached.
+    #[arg(long, global = true, default_value_t = 20)]
+    pub confirmations: i32,
+}
+
+impl CacheArgs {
+    /// Open the cache for every later [`crate::run_graphql`], unless
+    /// `--no-cache` was given.
+    pub fn install(&self) -> anyhow::Result<()> {
+        if self.no_cache {
+            return Ok(());
+        }
+
+        Cache::open(
+            self.cache_dir.clone(),
+            Duration::from_secs(self.cache_ttl),
+            self.cache_max_mib * 1024 * 1024,
+            self.confirmations,
+        )?
+        .install()
+    }
+
+    /// Deployment and block to export at, `--block` if given and the head
+    /// otherwise.
+    pub async fn meta(&self) -> anyhow::Result<Meta> {
+        let head = meta::query_meta().await;
+        let Some(block) = self.block else {
+            return Ok(head);
+        };
+
+        anyhow::ensure!(
+            block <= head.block_number,
+            "block {block} is past the indexed head {}",
+            head.block_number
+        );
+        meta::query_meta_at(block).await
+    }
+}
+
+pub struct Cache {
+    dir: PathBuf,
+    ttl: Duration,
+    max_size: u64,
+    confirmations: i32,
+    /// Bytes on disk, kept up to date by [`Cache::put`] so the directory is
+    /// only scanned when it has to be pruned.
+    size: AtomicU64,
+}
+
+impl Cache {
+    /// Create `dir` if needed and drop whatever is expired or over
+    /// `max_size` bytes.
+    pub fn open(
+        dir: PathBuf,
+        ttl: Duration,
+        max_size: u64,
+        confirmations: i32,
+    ) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&dir)?;
+        let cache = Self {
+            dir,
+            ttl,
+            max_size,
+            confirmations,
+            size: AtomicU64::new(0),
+        };
+        cache.prune()?;
+        Ok(cache)
+    }
+
+    /// Use this cache for every later [`crate::run_graphql`].
+    pub fn install(self) -> anyhow::Result<()> {
+        CACHE
+            .set(self)
+            .map_err(|_| anyhow::anyhow!("the response cache is already installed"))
+    }
+
+    /// Key of a query, `None` when it is not pinned to a block number deep
+    /// enough below the head.
+    fn key<Vars: Serialize>(&self, query: &str, variables: &Vars) -> Option<String> {
+        let variables = serde_json::to_value(variables).ok()?;
+        let block = variables.get("block")?.get("number")?.as_i64()?;
+
+        let head = HEAD.read().ok()?;
+        let head = head.as_ref()?;
+        if block > i64::from(head.block_number) - i64::from(self.confirmations) {
+            return None;
+        }
+
+        let content = serde_json::to_vec(&(&head.deployment, query, &variables)).ok()?;
+        Some(output::checksum(&content))
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.json"))
+    }
+
+    fn is_expired(&self, modified: SystemTime) -> bool {
+        modified.elapsed().is_ok_and(|age| age > self.ttl)
+    }
+
+    async fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let path = self.path(key);
+        let modified = tokio::fs::metadata(&path).await.ok()?.modified().ok()?;
+        if self.is_expired(modified) {
+            return None;
+        }
+        tokio::fs::read(&path).await.ok()
+    }
+
+    async fn put(&self, key: &str, body: &[u8]) -> anyhow::Result<()> {
+        // written aside and renamed, so a concurrent run never reads half a
+        // response
+        let path = self.path(key);
+        let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+        tokio::fs::write(&tmp, body).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+
+        let size = self.size.fetch_add(body.len() as u64, Ordering::Relaxed) + body.len() as u64;
+        if size > self.max_size {
+            self.prune()?;
+        }
+        Ok(())
+    }
+
+    /// Remove expired responses, then the oldest ones until the cache fits
+    /// in `max_size`.
+    fn prune(&self) -> anyhow::Result<()> {
+        let mut entries = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_file() {
+                entries.push((metadata.modified()?, metadata.len(), entry.path()));
+            }
+        }
+        entries.sort();
+
+        let mut size = entries.iter().map(|(_, len, _)| len).sum::<u64>();
+        for (modified, len, path) in entries {
+            if size <= self.max_size && !self.is_expired(modified) {
+                continue;
+            }
+            std::fs::remove_file(path)?;
+            size -= len;
+        }
+
+        self.size.store(size, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Record the subgraph's deployment and head, which later keys depend on.
+pub(crate) fn set_head(meta: &Meta) {
+    if let Ok(mut head) = HEAD.write() {
+        *head = Some(Head {
+            deployment: meta.deployment.clone(),
+            block_number: meta.block_number,
+        });
+    }
+}
+
+/// Cached response body of the query, if any.
+pub(crate) async fn get<Vars: Serialize>(query: &str, variables: &Vars) -> Option<Vec<u8>> {
+    let cache = CACHE.get()?;
+    cache.get(&cache.key(query, variables)?).await
+}
+
+/// Store a response body. Failing to is not fatal, the query already
+/// succeeded.
+pub(crate) async fn put<Vars: Serialize>(query: &str, variables: &Vars, body: &[u8]) {
+    let Some(cache) = CACHE.get() else {
+        return;
+    };
+    let Some(key) = cache.key(query, variables) else {
+        return;
+    };
+    if let Err(e) = cache.put(&key, body).await {
+        eprintln!("failed to cache response: {e}");
+    }
+}
@@ -0,0 +1,91 @@
+//! Subdomain capacity utilisation per origin domain.
+//!
+//! A name whose `origin` is another domain draws its subdomains from that
+//! domain's capacity, so usage is summed over every record sharing an origin.
+use std::{collections::BTreeMap, str::FromStr};
+
+use serde::Serialize;
+
+use crate::registrations::Record;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Utilisation {
+    pub origin: String,
+    pub capacity: i64,
+    /// Subdomains of the origin and of every name delegating to it.
+    pub used: i64,
+    pub ratio: f64,
+    pub at_or_over: bool,
+    /// Names other than the origin itself drawing on its capacity.
+    pub delegated: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SortBy {
+    Ratio,
+    Used,
+    Capacity,
+}
+
+impl FromStr for SortBy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ratio" => Ok(SortBy::Ratio),
+            "used" => Ok(SortBy::Used),
+            "capacity" => Ok(SortBy::Capacity),
+            _ => anyhow::bail!("unknown sort `{s}`, expected ratio, used or capacity"),
+        }
+    }
+}
+
+/// Utilisation of every origin with at least `min_ratio` used, highest first.
+pub fn utilisation(
+    records: &BTreeMap<String, Record>,
+    sort_by: SortBy,
+    min_ratio: f64,
+) -> Vec<Utilisation> {
+    let mut origins = BTreeMap::<&str, (i64, Vec<String>)>::new();
+
+    for (id, record) in records {
+        let (used, delegated) = origins.entry(record.origin.as_str()).or_default();
+        *used += record.children as i64;
+        if record.origin != *id {
+            delegated.push(id.clone());
+        }
+    }
+
+    let mut res = origins
+        .into_iter()
+        .map(|(origin, (used, mut delegated))| {
+            delegated.sort();
+            let capacity = records.get(origin).map_or(100, |r| r.capacity);
+            let ratio = if capacity > 0 {
+                used as f64 / capacity as f64
+            } else {
+                f64::INFINITY
+            };
+
+            Utilisation {
+                origin: origin.into(),
+                capacity,
+                used,
+                ratio,
+                at_or_over: used >= capacity,
+                delegated,
+            }
+        })
+        .filter(|u| u.ratio >= min_ratio)
+        .collect::<Vec<_>>();
+
+    match sort_by {
+        SortBy::Ratio => res.sort_by(|a, b| b.ratio.total_cmp(&a.ratio)),
+        SortBy::Used => res.sort_by_key(|u| std::cmp::Reverse(u.used)),
+        SortBy::Capacity => res.sort_by_key(|u| std::cmp::Reverse(u.capacity)),
+    }
+
+    res
+}
@@ -0,0 +1,401 @@
+//! Arrow schemas for the typed records and Parquet output.
+//!
+//! Ids are stored as fixed-size binary, timestamps as `UInt64` seconds and
+//! wei amounts as `Decimal256(76, 0)`, so lake tools get real types instead
+//! of JSON strings. [`columnar_row!`](crate::columnar_row) derives a row
+//! type's schema and columns from its fields and their [`Kind`]s.
+use std::{marker::PhantomData, sync::Arc};
+
+use arrow_array::{
+    builder::{
+        ArrayBuilder, Decimal256Builder, FixedSizeBinaryBuilder, StringBuilder, UInt32Builder,
+        UInt64Builder,
+    },
+    types::{Decimal256Type, DecimalType},
+    ArrayRef, RecordBatch,
+};
+use arrow_buffer::i256;
+use arrow_schema::{DataType, Field, Schema};
+use parquet::{
+    arrow::ArrowWriter,
+    basic::Compression,
+    file::{metadata::KeyValue, properties::WriterProperties},
+};
+
+use crate::{
+    envelope::{self, Header},
+    output,
+    resolvers::hex_decode,
+};
+
+pub const DOMAIN_ID_BYTES: i32 = 32;
+pub const ACCOUNT_ID_BYTES: i32 = 20;
+
+/// A dataset with a fixed Arrow schema, written as one record batch.
+pub trait Columnar {
+    fn schema() -> Schema;
+
+    fn to_batch(&self) -> anyhow::Result<RecordBatch>;
+}
+
+/// A row type whose fields map to columns, see [`columnar_row!`](crate::columnar_row).
+pub trait ColumnarRow: Sized {
+    fn fields() -> Vec<Field>;
+
+    fn columns(rows: &[Self]) -> anyhow::Result<Vec<ArrayRef>>;
+}
+
+/// How a column is stored.
+pub trait Kind {
+    type Builder: ArrayBuilder;
+
+    fn data_type() -> DataType;
+
+    fn builder(capacity: usize) -> Self::Builder;
+
+    fn append_null(builder: &mut Self::Builder);
+}
+
+/// A field value that can be stored as a `K` column. `Option`s make the
+/// column nullable.
+pub trait Cell<K: Kind> {
+    const NULLABLE: bool = false;
+
+    fn append(&self, builder: &mut K::Builder) -> anyhow::Result<()>;
+}
+
+/// UTF-8 text.
+pub struct Text;
+
+/// `0x` prefixed hex of `N` bytes, as normalized by [`crate::HandleId`].
+pub struct FixedBytes<const N: i32>;
+
+pub type DomainId = FixedBytes<DOMAIN_ID_BYTES>;
+pub type AccountId = FixedBytes<ACCOUNT_ID_BYTES>;
+/// Labelhashes and transaction hashes.
+pub type Hash = FixedBytes<32>;
+
+/// Counts and block numbers, which the subgraph types as `Int`.
+pub struct U32;
+
+/// Unix seconds and capacities.
+pub struct U64;
+
+/// Decimal wei strings as returned by the subgraph.
+pub struct Wei;
+
+impl Kind for Text {
+    type Builder = StringBuilder;
+
+    fn data_type() -> DataType {
+        DataType::Utf8
+    }
+
+    fn builder(capacity: usize) -> Self::Builder {
+        StringBuilder::with_capacity(capacity, capacity * 16)
+    }
+
+    fn append_null(builder: &mut Self::Builder) {
+        builder.append_null()
+    }
+}
+
+impl<const N: i32> Kind for FixedBytes<N> {
+    type Builder = FixedSizeBinaryBuilder;
+
+    fn data_type() -> DataType {
+        DataType::FixedSizeBinary(N)
+    }
+
+    fn builder(capacity: usize) -> Self::Builder {
+        FixedSizeBinaryBuilder::with_capacity(capacity, N)
+    }
+
+    fn append_null(builder: &mut Self::Builder) {
+        builder.append_null()
+    }
+}
+
+impl Kind for U32 {
+    type Builder = UInt32Builder;
+
+    fn data_type() -> DataType {
+        DataType::UInt32
+    }
+
+    fn builder(capacity: usize) -> Self::Builder {
+        UInt32Builder::with_capacity(capacity)
+    }
+
+    fn append_null(builder: &mut Self::Builder) {
+        builder.append_null()
+    }
+}
+
+impl Kind for U64 {
+    type Builder = UInt64Builder;
+
+    fn data_type() -> DataType {
+        DataType::UInt64
+    }
+
+    fn builder(capacity: usize) -> Self::Builder {
+        UInt64Builder::with_capacity(capacity)
+    }
+
+    fn append_null(builder: &mut Self::Builder) {
+        builder.append_null()
+    }
+}
+
+impl Kind for Wei {
+    type Builder = Decimal256Builder;
+
+    fn data_type() -> DataType {
+        // a uint256 has up to 78 digits, 76 is the most Arrow allows
+        DataType::Decimal256(76, 0)
+    }
+
+    fn builder(capacity: usize) -> Self::Builder {
+        Decimal256Builder::with_capacity(capacity).with_data_type(Self::data_type())
+    }
+
+    fn append_null(builder: &mut Self::Builder) {
+        builder.append_null()
+    }
+}
+
+impl<K: Kind, T: Cell<K>> Cell<K> for Option<T> {
+    const NULLABLE: bool = true;
+
+    fn append(&self, builder: &mut K::Builder) -> anyhow::Result<()> {
+        match self {
+            Some(value) => value.append(builder),
+            None => {
+                K::append_null(builder);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<K: Kind, T: Cell<K> + ?Sized> Cell<K> for &T {
+    const NULLABLE: bool = T::NULLABLE;
+
+    fn append(&self, builder: &mut K::Builder) -> anyhow::Result<()> {
+        (**self).append(builder)
+    }
+}
+
+impl<K: Kind> Cell<K> for String
+where
+    str: Cell<K>,
+{
+    fn append(&self, builder: &mut K::Builder) -> anyhow::Result<()> {
+        self.as_str().append(builder)
+    }
+}
+
+impl Cell<Text> for str {
+    fn append(&self, builder: &mut StringBuilder) -> anyhow::Result<()> {
+        builder.append_value(self);
+        Ok(())
+    }
+}
+
+impl<const N: i32> Cell<FixedBytes<N>> for str {
+    fn append(&self, builder: &mut FixedSizeBinaryBuilder) -> anyhow::Result<()> {
+        let bytes = self
+            .strip_prefix("0x")
+            .and_then(hex_decode)
+            .ok_or_else(|| anyhow::anyhow!("`{self}` is not a hex id"))?;
+        Ok(builder.append_value(bytes)?)
+    }
+}
+
+impl Cell<U32> for i32 {
+    fn append(&self, builder: &mut UInt32Builder) -> anyhow::Result<()> {
+        let value =
+            u32::try_from(*self).map_err(|_| anyhow::anyhow!("{self} is negative, not a count"))?;
+        builder.append_value(value);
+        Ok(())
+    }
+}
+
+impl Cell<U64> for i64 {
+    fn append(&self, builder: &mut UInt64Builder) -> anyhow::Result<()> {
+        let value = u64::try_from(*self).map_err(|_| anyhow::anyhow!("{self} is negative"))?;
+        builder.append_value(value);
+        Ok(())
+    }
+}
+
+impl Cell<Wei> for str {
+    fn append(&self, builder: &mut Decimal256Builder) -> anyhow::Result<()> {
+        let value = self
+            .parse::<i256>()
+            .map_err(|_| anyhow::anyhow!("`{self}` is not a wei amount"))?;
+        Decimal256Type::validate_decimal_precision(value, 76)?;
+        builder.append_value(value);
+        Ok(())
+    }
+}
+
+/// The Arrow field of a row field with `cell`'s type, stored as `K`.
+pub fn field<R, K: Kind, C: Cell<K> + ?Sized>(
+    name: &str,
+    _cell: impl Fn(&R) -> &C,
+    _kind: PhantomData<K>,
+) -> Field {
+    Field::new(name, K::data_type(), C::NULLABLE)
+}
+
+/// One column of `rows`, with the value `cell` picks from each.
+pub fn column<R, K: Kind, C: Cell<K> + ?Sized>(
+    rows: &[R],
+    cell: impl Fn(&R) -> &C,
+    _kind: PhantomData<K>,
+) -> anyhow::Result<ArrayRef> {
+    let mut builder = K::builder(rows.len());
+    for row in rows {
+        cell(row).append(&mut builder)?;
+    }
+    Ok(builder.finish())
+}
+
+/// Implement [`ColumnarRow`] for a struct, one column per listed field in
+/// the given order, named after the field and stored as the given [`Kind`].
+/// `Option` fields give nullable columns.
+///
+/// ```ignore
+/// columnar_row!(Record<'_> { id: DomainId, expire: U64 });
+/// ```
+#[macro_export]
+macro_rules! columnar_row {
+    ($row:ty { $($field:ident: $kind:ty),* $(,)? }) => {
+        impl $crate::columnar::ColumnarRow for $row {
+            fn fields() -> Vec<arrow_schema::Field> {
+                vec![$(
+                    $crate::columnar::field(
+                        stringify!($field),
+                        |row: &Self| &row.$field,
+                        std::marker::PhantomData::<$kind>,
+                    ),
+                )*]
+            }
+
+            fn columns(rows: &[Self]) -> anyhow::Result<Vec<arrow_array::ArrayRef>> {
+                Ok(vec![$(
+                    $crate::columnar::column(
+                        rows,
+                        |row: &Self| &row.$field,
+                        std::marker::PhantomData::<$kind>,
+                    )?,
+                )*])
+            }
+        }
+    };
+}
+
+/// The schema of `R`'s columns.
+pub fn schema<R: ColumnarRow>() -> Schema {
+    Schema::new(R::fields())
+}
+
+/// One batch holding `rows`.
+pub fn to_batch<R: ColumnarRow>(rows: &[R]) -> anyhow::Result<RecordBatch> {
+    Ok(RecordBatch::try_new(
+        Arc::new(schema::<R>()),
+        R::columns(rows)?,
+    )?)
+}
+
+/// `header` is kept as JSON under [`envelope::PARQUET_KEY`] in the file's
+/// key/value metadata, with the checksum of the file written without it.
+pub fn to_parquet<T: Columnar>(header: &Header, value: &T) -> anyhow::Result<Vec<u8>> {
+    let batch = value.to_batch()?;
+    let header = header
+        .clone()
+        .with_checksum(output::checksum(&write_batch(&batch, None)?));
+
+    write_batch(
+        &batch,
+        Some(vec![KeyValue::new(
+            envelope::PARQUET_KEY.to_string(),
+            serde_json::to_string(&header)?,
+        )]),
+    )
+}
+
+fn write_batch(batch: &RecordBatch, metadata: Option<Vec<KeyValue>>) -> anyhow::Result<Vec<u8>> {
+    let props = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .set_key_value_metadata(metadata)
+        .build();
+
+    let mut res = Vec::new();
+    let mut writer = ArrowWriter::try_new(&mut res, batch.schema(), Some(props))?;
+    writer.write(batch)?;
+    writer.close()?;
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Row {
+        id: String,
+        cost: Option<String>,
+        expire: Option<i64>,
+    }
+
+    crate::columnar_row!(Row {
+        id: DomainId,
+        cost: Wei,
+        expire: U64,
+    });
+
+    fn row(cost: &str, expire: i64) -> Row {
+        Row {
+            id: format!("0x{}", "01".repeat(32)),
+            cost: Some(cost.to_string()),
+            expire: Some(expire),
+        }
+    }
+
+    #[test]
+    fn schema_follows_the_fields() {
+        assert_eq!(
+            schema::<Row>(),
+            Schema::new(vec![
+                Field::new("id", DataType::FixedSizeBinary(32), false),
+                Field::new("cost", DataType::Decimal256(76, 0), true),
+                Field::new("expire", DataType::UInt64, true),
+            ])
+        );
+
+        let rows = [
+            row(&"9".repeat(76), 1_667_908_800),
+            Row {
+                cost: None,
+                expire: None,
+                ..row("0", 0)
+            },
+        ];
+        let batch = to_batch(&rows).unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(batch.column(1).null_count(), 1);
+    }
+
+    #[test]
+    fn out_of_range_values_are_errors() {
+        assert!(to_batch(&[row(&"9".repeat(77), 0)]).is_err());
+        assert!(to_batch(&[row("1", -1)]).is_err());
+        assert!(to_batch(&[Row {
+            id: "0x01".to_string(),
+            ..row("1", 0)
+        }])
+        .is_err());
+    }
+}
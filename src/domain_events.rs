@@ -0,0 +1,440 @@
+/*!
+```
+query QueryDomainEvents($skip: Int = 10, $block: Block_height, $filter: DomainEvent_filter) {
+  domainEvents(
+    skip: $skip
+    first: 1000
+    block: $block
+    where: $filter
+    orderBy: blockNumber
+    orderDirection: asc
+  ) {
+    ... on Transfer {
+      ...EventFields
+      from {
+        id
+      }
+      to {
+        id
+      }
+    }
+    ... on NewResolver {
+      ...EventFields
+      resolver {
+        address
+      }
+    }
+    ... on NewSubdomain {
+      ...EventFields
+      name
+      to {
+        id
+      }
+      parentId {
+        id
+        name
+        createdAt
+      }
+    }
+    ... on Set {
+      ...EventFields
+      keyHash
+      value
+    }
+    ... on SetLink {
+      ...EventFields
+      keyHash
+      value
+    }
+    ... on SetNftName {
+      ...EventFields
+      nftTokenId
+      nftAddr {
+        id
+      }
+    }
+  }
+}
+
+fragment EventFields on DomainEvent {
+  id
+  blockNumber
+  triggeredDate
+  transactionID
+  domain {
+    id
+    name
+    createdAt
+  }
+}
+```
+*/
+#[cynic::schema_for_derives(file = r#"schema.gql"#, module = "schema")]
+mod queries {
+    use crate::{
+        fragments::{AccountFields, BigInt, BlockHeight, Bytes, DomainFields, DomainFilter},
+        schema,
+    };
+
+    #[derive(cynic::QueryVariables, Debug)]
+    pub struct QueryDomainEventsVariables {
+        pub skip: Option<i32>,
+        pub block: Option<BlockHeight>,
+        pub filter: Option<DomainEventFilter>,
+    }
+
+    #[derive(cynic::InputObject, Debug, Default, Clone)]
+    #[cynic(graphql_type = "DomainEvent_filter")]
+    pub struct DomainEventFilter {
+        #[cynic(skip_serializing_if = "Option::is_none")]
+        pub domain: Option<String>,
+        #[cynic(rename = "domain_", skip_serializing_if = "Option::is_none")]
+        pub domain_: Option<DomainFilter>,
+        #[cynic(rename = "blockNumber_gt", skip_serializing_if = "Option::is_none")]
+        pub block_number_gt: Option<i32>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    #[cynic(graphql_type = "Query", variables = "QueryDomainEventsVariables")]
+    pub struct QueryDomainEvents {
+        #[arguments(skip: $skip, first: 1000, block: $block, where: $filter, orderBy: blockNumber, orderDirection: asc)]
+        pub domain_events: Vec<DomainEvent>,
+    }
+
+    #[derive(cynic::InlineFragments, Debug)]
+    pub enum DomainEvent {
+        Transfer(Transfer),
+        NewResolver(NewResolver),
+        NewSubdomain(NewSubdomain),
+        Set(Set),
+        SetLink(SetLink),
+        SetNftName(SetNftName),
+        #[cynic(fallback)]
+        Unknown,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    pub struct Transfer {
+        pub id: cynic::Id,
+        pub block_number: i32,
+        pub triggered_date: BigInt,
+        #[cynic(rename = "transactionID")]
+        pub transaction_id: Bytes,
+        pub domain: DomainFields,
+        pub from: Option<AccountFields>,
+        pub to: AccountFields,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    pub struct NewResolver {
+        pub id: cynic::Id,
+        pub block_number: i32,
+        pub triggered_date: BigInt,
+        #[cynic(rename = "transactionID")]
+        pub transaction_id: Bytes,
+        pub domain: DomainFields,
+        pub resolver: Resolver,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    pub struct Resolver {
+        pub address: Bytes,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    pub struct NewSubdomain {
+        pub id: cynic::Id,
+        pub block_number: i32,
+        pub triggered_date: BigInt,
+        #[cynic(rename = "transactionID")]
+        pub transaction_id: Bytes,
+        pub domain: DomainFields,
+        pub name: String,
+        pub to: AccountFields,
+        pub parent_id: DomainFields,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    pub struct Set {
+        pub id: cynic::Id,
+        pub block_number: i32,
+        pub triggered_date: BigInt,
+        #[cynic(rename = "transactionID")]
+        pub transaction_id: Bytes,
+        pub domain: DomainFields,
+        pub key_hash: BigInt,
+        pub value: String,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    pub struct SetLink {
+        pub id: cynic::Id,
+        pub block_number: i32,
+        pub triggered_date: BigInt,
+        #[cynic(rename = "transactionID")]
+        pub transaction_id: Bytes,
+        pub domain: DomainFields,
+        pub key_hash: BigInt,
+        pub value: BigInt,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    pub struct SetNftName {
+        pub id: cynic::Id,
+        pub block_number: i32,
+        pub triggered_date: BigInt,
+        #[cynic(rename = "transactionID")]
+        pub transaction_id: Bytes,
+        pub domain: DomainFields,
+        pub nft_token_id: BigInt,
+        pub nft_addr: AccountFields,
+    }
+}
+
+use serde::Serialize;
+
+use crate::{
+    fragments::{AccountFields, BlockHeight, DomainFilter},
+    query_pages, query_pages_each, BuildQuery, HandleId, IsFull, ACCOUNT_ID_LEN, DOMAIN_ID_LEN,
+};
+
+/// What happened, with the fields specific to each `DomainEvent` type.
+#[derive(Debug, Serialize, Clone)]
+#[serde(tag = "type")]
+pub enum DomainEventKind {
+    Transfer {
+        from: Option<String>,
+        to: String,
+    },
+    NewResolver {
+        resolver: String,
+    },
+    NewSubdomain {
+        parent: String,
+        name: String,
+        to: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    Set {
+        key_hash: String,
+        value: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    SetLink {
+        key_hash: String,
+        value: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    SetNftName {
+        nft_addr: String,
+        nft_token_id: String,
+    },
+}
+
+impl DomainEventKind {
+    /// The `DomainEvent` type, as in the `type` field.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            DomainEventKind::Transfer { .. } => "Transfer",
+            DomainEventKind::NewResolver { .. } => "NewResolver",
+            DomainEventKind::NewSubdomain { .. } => "NewSubdomain",
+            DomainEventKind::Set { .. } => "Set",
+            DomainEventKind::SetLink { .. } => "SetLink",
+            DomainEventKind::SetNftName { .. } => "SetNftName",
+        }
+    }
+
+    /// Accounts taking part in the event: both sides of a transfer and the
+    /// owner of a new subdomain.
+    pub fn accounts(&self) -> impl Iterator<Item = &str> {
+        let accounts = match self {
+            DomainEventKind::Transfer { from, to } => [from.as_deref(), Some(to.as_str())],
+            DomainEventKind::NewSubdomain { to, .. } => [Some(to.as_str()), None],
+            _ => [None, None],
+        };
+        accounts.into_iter().flatten()
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DomainEventRecord {
+    pub id: String,
+    pub domain: String,
+    pub name: Option<String>,
+    pub block_number: i32,
+    pub triggered_date: i64,
+    pub transaction_id: String,
+    #[serde(flatten)]
+    pub event: DomainEventKind,
+}
+
+pub struct DomainEventsBuilder;
+
+impl DomainEventsBuilder {
+    fn build_query_filtered(
+        offset: i32,
+        block: i32,
+        filter: queries::DomainEventFilter,
+    ) -> cynic::Operation<queries::QueryDomainEvents, queries::QueryDomainEventsVariables> {
+        <queries::QueryDomainEvents as cynic::QueryBuilder>::build(
+            queries::QueryDomainEventsVariables {
+                skip: Some(offset),
+                block: Some(BlockHeight::number(block)),
+                filter: Some(filter),
+            },
+        )
+    }
+
+    /// Ordered history of one domain, given by id (`0x...`) or full name.
+    pub async fn query(domain: &str, block: i32) -> Vec<DomainEventRecord> {
+        let filter = Self::filter(domain);
+        query_pages(|offset| Self::build_query_filtered(offset, block, filter.clone())).await
+    }
+
+    /// Events of every domain in any block after `after`, up to `block`.
+    pub async fn query_after(after: i32, block: i32) -> Vec<DomainEventRecord> {
+        let filter = queries::DomainEventFilter {
+            block_number_gt: Some(after),
+            ..Default::default()
+        };
+
+        query_pages(|offset| Self::build_query_filtered(offset, block, filter.clone())).await
+    }
+
+    /// Like [`DomainEventsBuilder::query`] but hands every event to
+    /// `on_event` as its page arrives.
+    pub async fn query_each(
+        domain: &str,
+        block: i32,
+        on_event: impl FnMut(DomainEventRecord) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let filter = Self::filter(domain);
+        query_pages_each(
+            |offset| Self::build_query_filtered(offset, block, filter.clone()),
+            on_event,
+        )
+        .await
+    }
+
+    fn filter(domain: &str) -> queries::DomainEventFilter {
+        if domain.starts_with("0x") {
+            queries::DomainEventFilter {
+                domain: Some(domain.to_lowercase()),
+                ..Default::default()
+            }
+        } else {
+            queries::DomainEventFilter {
+                domain_: Some(DomainFilter {
+                    name: Some(domain.into()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }
+        }
+    }
+}
+
+impl BuildQuery for DomainEventsBuilder {
+    type Vars = queries::QueryDomainEventsVariables;
+
+    type ResponseData = queries::QueryDomainEvents;
+
+    fn build_query(offset: i32, block: i32) -> cynic::Operation<Self::ResponseData, Self::Vars> {
+        Self::build_query_filtered(offset, block, Default::default())
+    }
+}
+
+impl IsFull for queries::QueryDomainEvents {
+    type Item = DomainEventRecord;
+
+    fn len(&self) -> usize {
+        self.domain_events.len()
+    }
+
+    fn into_iter(self) -> impl IntoIterator<Item = Self::Item> {
+        IntoIterator::into_iter(self.domain_events).filter_map(|event| {
+            use queries::DomainEvent as E;
+
+            let account = |a: AccountFields| a.id.0.handle_id::<ACCOUNT_ID_LEN>();
+            let (id, block_number, triggered_date, transaction_id, domain, event) = match event {
+                E::Transfer(e) => (
+                    e.id,
+                    e.block_number,
+                    e.triggered_date,
+                    e.transaction_id,
+                    e.domain,
+                    DomainEventKind::Transfer {
+                        from: e.from.map(account),
+                        to: account(e.to),
+                    },
+                ),
+                E::NewResolver(e) => (
+                    e.id,
+                    e.block_number,
+                    e.triggered_date,
+                    e.transaction_id,
+                    e.domain,
+                    DomainEventKind::NewResolver {
+                        resolver: e.resolver.address.0.handle_id::<ACCOUNT_ID_LEN>(),
+                    },
+                ),
+                E::NewSubdomain(e) => (
+                    e.id,
+                    e.block_number,
+                    e.triggered_date,
+                    e.transaction_id,
+                    e.domain,
+                    DomainEventKind::NewSubdomain {
+                        parent: e.parent_id.id.0.handle_id::<DOMAIN_ID_LEN>(),
+                        name: e.name,
+                        to: account(e.to),
+                    },
+                ),
+                E::Set(e) => (
+                    e.id,
+                    e.block_number,
+                    e.triggered_date,
+                    e.transaction_id,
+                    e.domain,
+                    DomainEventKind::Set {
+                        key_hash: e.key_hash.0,
+                        value: e.value,
+                    },
+                ),
+                E::SetLink(e) => (
+                    e.id,
+                    e.block_number,
+                    e.triggered_date,
+                    e.transaction_id,
+                    e.domain,
+                    DomainEventKind::SetLink {
+                        key_hash: e.key_hash.0,
+                        value: e.value.0,
+                    },
+                ),
+                E::SetNftName(e) => (
+                    e.id,
+                    e.block_number,
+                    e.triggered_date,
+                    e.transaction_id,
+                    e.domain,
+                    DomainEventKind::SetNftName {
+                        nft_addr: account(e.nft_addr),
+                        nft_token_id: e.nft_token_id.0,
+                    },
+                ),
+                E::Unknown => return None,
+            };
+            Some(DomainEventRecord {
+                id: id.inner().to_string(),
+                domain: domain.id.0.handle_id::<DOMAIN_ID_LEN>(),
+                name: domain.name,
+                block_number,
+                triggered_date: triggered_date.0.parse().unwrap(),
+                transaction_id: transaction_id.0,
+                event,
+            })
+        })
+    }
+}
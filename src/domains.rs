@@ -0,0 +1,258 @@
+/*!
+```
+query QueryDomains($skip: Int = 10, $block: Block_height, $filter: Domain_filter) {
+  domains(skip: $skip, first: 1000, block: $block, where: $filter) {
+    id
+    name
+    labelName
+    labelhash
+    parent {
+      id
+      name
+      createdAt
+    }
+    owner {
+      id
+    }
+    resolvedAddress {
+      id
+    }
+    resolver {
+      address
+    }
+    subdomainCount
+    createdAt
+    registrations(first: 1, orderBy: expiryDate, orderDirection: desc) {
+      expiryDate
+      capacity
+    }
+  }
+}
+```
+*/
+#[cynic::schema_for_derives(file = r#"schema.gql"#, module = "schema")]
+mod queries {
+    use crate::{
+        fragments::{AccountFields, BigInt, BlockHeight, Bytes, DomainFields, DomainFilter},
+        schema,
+    };
+
+    #[derive(cynic::QueryVariables, Debug)]
+    pub struct QueryDomainsVariables {
+        pub skip: Option<i32>,
+        pub block: Option<BlockHeight>,
+        pub filter: Option<DomainFilter>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    #[cynic(graphql_type = "Query", variables = "QueryDomainsVariables")]
+    pub struct QueryDomains {
+        #[arguments(skip: $skip, first: 1000, block: $block, where: $filter)]
+        pub domains: Vec<Domain>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    pub struct Domain {
+        pub id: Bytes,
+        pub name: Option<String>,
+        pub label_name: Option<String>,
+        pub labelhash: Option<Bytes>,
+        pub parent: Option<DomainFields>,
+        pub owner: Option<AccountFields>,
+        pub resolved_address: Option<AccountFields>,
+        pub resolver: Option<Resolver>,
+        pub subdomain_count: i32,
+        pub created_at: BigInt,
+        #[arguments(first: 1, orderBy: expiryDate, orderDirection: desc)]
+        pub registrations: Option<Vec<Registration>>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    pub struct Resolver {
+        pub address: Bytes,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    pub struct Registration {
+        pub expiry_date: Option<BigInt>,
+        pub capacity: Option<BigInt>,
+    }
+}
+
+use anyhow::Context;
+use arrow_array::RecordBatch;
+use arrow_schema::Schema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    columnar::{self, AccountId, Columnar, DomainId, Hash, Text, U32, U64},
+    fragments::{BlockChangedFilter, BlockHeight, DomainFilter},
+    output::Tabular,
+    query_all_at, query_pages, BuildQuery, HandleId, IsFull, ACCOUNT_ID_LEN, DOMAIN_ID_LEN,
+};
+
+/// A domain joined with its owner, resolver, parent and latest registration.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DomainRecord {
+    pub id: String,
+    pub name: Option<String>,
+    pub label_name: Option<String>,
+    pub labelhash: Option<String>,
+    pub parent: Option<String>,
+    pub owner: Option<String>,
+    pub resolved_address: Option<String>,
+    pub resolver: Option<String>,
+    pub subdomain_count: i32,
+    pub created_at: i64,
+    pub expire: Option<i64>,
+    pub capacity: Option<i64>,
+}
+
+impl Tabular for Vec<DomainRecord> {
+    type Row<'a> = &'a DomainRecord;
+
+    fn rows(&self) -> impl Iterator<Item = Self::Row<'_>> {
+        self.iter()
+    }
+}
+
+crate::columnar_row!(DomainRecord {
+    id: DomainId,
+    name: Text,
+    label_name: Text,
+    labelhash: Hash,
+    parent: DomainId,
+    owner: AccountId,
+    resolved_address: AccountId,
+    resolver: AccountId,
+    subdomain_count: U32,
+    created_at: U64,
+    expire: U64,
+    capacity: U64,
+});
+
+impl Columnar for Vec<DomainRecord> {
+    fn schema() -> Schema {
+        columnar::schema::<DomainRecord>()
+    }
+
+    fn to_batch(&self) -> anyhow::Result<RecordBatch> {
+        columnar::to_batch(self)
+    }
+}
+
+pub struct DomainsBuilder;
+
+impl DomainsBuilder {
+    fn build_query_filtered(
+        offset: i32,
+        block: i32,
+        filter: DomainFilter,
+    ) -> cynic::Operation<queries::QueryDomains, queries::QueryDomainsVariables> {
+        <queries::QueryDomains as cynic::QueryBuilder>::build(queries::QueryDomainsVariables {
+            skip: Some(offset),
+            block: Some(BlockHeight::number(block)),
+            filter: Some(filter),
+        })
+    }
+
+    /// Every domain at `block`.
+    pub async fn query(block: i32) -> anyhow::Result<Vec<DomainRecord>> {
+        query_all_at::<Self>(block).await.into_iter().collect()
+    }
+
+    /// Domains currently owned by `owner`, at `block`.
+    pub async fn query_owner(owner: &str, block: i32) -> anyhow::Result<Vec<DomainRecord>> {
+        let filter = DomainFilter {
+            owner: Some(owner.to_lowercase()),
+            ..Default::default()
+        };
+
+        query_pages(|offset| Self::build_query_filtered(offset, block, filter.clone()))
+            .await
+            .into_iter()
+            .collect()
+    }
+
+    /// Domains written in any block after `after`, as they are at `block`.
+    pub async fn query_changed(after: i32, block: i32) -> anyhow::Result<Vec<DomainRecord>> {
+        let filter = DomainFilter {
+            change_block: Some(BlockChangedFilter::after(after)),
+            ..Default::default()
+        };
+
+        query_pages(|offset| Self::build_query_filtered(offset, block, filter.clone()))
+            .await
+            .into_iter()
+            .collect()
+    }
+}
+
+impl BuildQuery for DomainsBuilder {
+    type Vars = queries::QueryDomainsVariables;
+
+    type ResponseData = queries::QueryDomains;
+
+    fn build_query(offset: i32, block: i32) -> cynic::Operation<Self::ResponseData, Self::Vars> {
+        Self::build_query_filtered(offset, block, Default::default())
+    }
+}
+
+impl IsFull for queries::QueryDomains {
+    /// A domain whose `createdAt` or capacity is not a number is an error.
+    type Item = anyhow::Result<DomainRecord>;
+
+    fn len(&self) -> usize {
+        self.domains.len()
+    }
+
+    fn into_iter(self) -> impl IntoIterator<Item = Self::Item> {
+        IntoIterator::into_iter(self.domains).map(|domain| {
+            let registration = domain.registrations.and_then(|r| r.into_iter().next());
+            let id = domain.id.0.handle_id::<DOMAIN_ID_LEN>();
+            let created_at = domain.created_at.0.parse().with_context(|| {
+                format!(
+                    "createdAt `{}` of {id} is not a number",
+                    domain.created_at.0
+                )
+            })?;
+            let capacity = registration
+                .as_ref()
+                .map(|r| match &r.capacity {
+                    Some(capacity) => capacity.0.parse().with_context(|| {
+                        format!("capacity `{}` of {id} is not a number", capacity.0)
+                    }),
+                    None => Ok(100),
+                })
+                .transpose()?;
+
+            Ok(DomainRecord {
+                id,
+                name: domain.name,
+                label_name: domain.label_name,
+                labelhash: domain
+                    .labelhash
+                    .map(|hash| hash.0.handle_id::<DOMAIN_ID_LEN>()),
+                parent: domain
+                    .parent
+                    .map(|parent| parent.id.0.handle_id::<DOMAIN_ID_LEN>()),
+                owner: domain
+                    .owner
+                    .map(|owner| owner.id.0.handle_id::<ACCOUNT_ID_LEN>()),
+                resolved_address: domain
+                    .resolved_address
+                    .map(|addr| addr.id.0.handle_id::<ACCOUNT_ID_LEN>()),
+                resolver: domain
+                    .resolver
+                    .map(|resolver| resolver.address.0.handle_id::<ACCOUNT_ID_LEN>()),
+                subdomain_count: domain.subdomain_count,
+                created_at,
+                expire: registration
+                    .and_then(|r| r.expiry_date)
+                    .and_then(|d| d.0.parse().ok()),
+                capacity,
+            })
+        })
+    }
+}
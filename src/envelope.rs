@@ -0,0 +1,128 @@
+//! Provenance written with every export and checked by the loaders.
+//!
+//! JSON exports are `{"header": .., "data": ..}`. Formats that cannot hold
+//! a JSON document keep the header in their own metadata: Parquet under
+//! [`PARQUET_KEY`] in the file's key/value metadata, SQLite in the
+//! `metadata` table, and CSV and NDJSON in a `.header.json` file next to
+//! them.
+//!
+//! The header also carries the SHA-256 of the records it describes, see
+//! [`Header::checksum`].
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{meta::Meta, output, ENDPOINT};
+
+/// Bumped whenever a dataset changes shape. Loaders refuse any other
+/// version.
+pub const FORMAT_VERSION: u32 = 1;
+
+pub const TOOL_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+pub const PARQUET_KEY: &str = "pns.header";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Header {
+    pub format_version: u32,
+    pub dataset: String,
+    pub endpoint: String,
+    pub deployment: String,
+    pub block_number: i32,
+    pub block_hash: Option<String>,
+    pub block_timestamp: Option<i32>,
+    /// Arguments that narrowed or shaped the export, such as a domain or a
+    /// time window. `null` for a full export.
+    pub params: serde_json::Value,
+    pub record_count: Option<usize>,
+    pub tool_version: String,
+    /// SHA-256 of the records, without the header: `data` as compact JSON,
+    /// the CSV or NDJSON file, or the Parquet file written without the
+    /// header. Unlike the `.sha256` file it does not depend on compression.
+    #[serde(default)]
+    pub checksum: Option<String>,
+}
+
+impl Header {
+    /// Header of `dataset` exported at the block of `meta`.
+    pub fn new(dataset: &str, meta: &Meta) -> Self {
+        Self {
+            format_version: FORMAT_VERSION,
+            dataset: dataset.to_string(),
+            endpoint: ENDPOINT.to_string(),
+            deployment: meta.deployment.clone(),
+            block_number: meta.block_number,
+            block_hash: meta.block_hash.clone(),
+            block_timestamp: meta.block_timestamp,
+            params: serde_json::Value::Null,
+            record_count: None,
+            tool_version: TOOL_VERSION.to_string(),
+            checksum: None,
+        }
+    }
+
+    pub fn with_params(mut self, params: serde_json::Value) -> Self {
+        self.params = params;
+        self
+    }
+
+    pub fn with_record_count(mut self, record_count: usize) -> Self {
+        self.record_count = Some(record_count);
+        self
+    }
+
+    pub fn with_checksum(mut self, checksum: String) -> Self {
+        self.checksum = Some(checksum);
+        self
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Envelope<T> {
+    pub header: Header,
+    pub data: T,
+}
+
+/// Just enough of a header to refuse a file before parsing the rest, whose
+/// shape may differ between versions.
+#[derive(Deserialize)]
+struct Versioned {
+    header: Option<VersionedHeader>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VersionedHeader {
+    format_version: u32,
+    dataset: String,
+}
+
+/// Parse a JSON export of `dataset`, refusing files without a header, of
+/// another dataset, written with another [`FORMAT_VERSION`] or whose data
+/// does not match [`Header::checksum`].
+pub fn open<T: DeserializeOwned>(bytes: &[u8], dataset: &str) -> anyhow::Result<Envelope<T>> {
+    let Some(header) = serde_json::from_slice::<Versioned>(bytes)?.header else {
+        anyhow::bail!("no header, `{dataset}` was exported before format version {FORMAT_VERSION}");
+    };
+    if header.format_version != FORMAT_VERSION {
+        anyhow::bail!(
+            "`{dataset}` has format version {}, expected {FORMAT_VERSION}",
+            header.format_version
+        );
+    }
+    if header.dataset != dataset {
+        anyhow::bail!("expected `{dataset}`, found `{}`", header.dataset);
+    }
+
+    let Envelope { header, data } = serde_json::from_slice::<Envelope<serde_json::Value>>(bytes)?;
+    if let Some(expected) = &header.checksum {
+        let actual = output::json_checksum(&data)?;
+        if actual != *expected {
+            anyhow::bail!("`{dataset}` has checksum {actual}, its header says {expected}");
+        }
+    }
+
+    Ok(Envelope {
+        header,
+        data: serde_json::from_value(data)?,
+    })
+}
@@ -0,0 +1,140 @@
+//! Expiry calendar built on `Registration.expiryDate`, grouped by owner so
+//! renewal reminders can go out per account.
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::domains::DomainRecord;
+
+const DAY: i64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ExpiryWindow {
+    /// Reference time, unix seconds.
+    pub now: i64,
+    /// Names expiring within this many days count as upcoming.
+    pub days: i64,
+    /// Days after expiry during which the owner can still renew.
+    pub grace_days: i64,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ExpiryStatus {
+    Expiring,
+    InGrace,
+    Expired,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpiryEntry {
+    pub id: String,
+    pub name: Option<String>,
+    pub expire: i64,
+    /// Whole days until expiry, negative once expired.
+    pub days_left: i64,
+}
+
+#[derive(Debug, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct OwnerExpiry {
+    pub expiring: Vec<ExpiryEntry>,
+    pub in_grace: Vec<ExpiryEntry>,
+    pub expired: Vec<ExpiryEntry>,
+}
+
+/// One owner of [`ExpiryReport::owners`], with the owner moved in.
+#[derive(Debug, Serialize)]
+pub struct OwnerExpiryRow<'a> {
+    pub owner: &'a str,
+    #[serde(flatten)]
+    pub expiry: &'a OwnerExpiry,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpiryReport {
+    pub now: i64,
+    pub days: i64,
+    pub grace_days: i64,
+    pub expiring_num: usize,
+    pub in_grace_num: usize,
+    pub expired_num: usize,
+    pub owners: BTreeMap<String, OwnerExpiry>,
+}
+
+impl ExpiryReport {
+    /// One row per owner, in owner order.
+    pub fn owner_rows(&self) -> impl Iterator<Item = OwnerExpiryRow<'_>> {
+        self.owners
+            .iter()
+            .map(|(owner, expiry)| OwnerExpiryRow { owner, expiry })
+    }
+}
+
+impl ExpiryWindow {
+    pub fn status(&self, expire: i64) -> Option<ExpiryStatus> {
+        if expire >= self.now {
+            (expire < self.now + self.days * DAY).then_some(ExpiryStatus::Expiring)
+        } else if self.now < expire + self.grace_days * DAY {
+            Some(ExpiryStatus::InGrace)
+        } else {
+            Some(ExpiryStatus::Expired)
+        }
+    }
+}
+
+/// Sort every owned, registered domain into upcoming, in grace or expired.
+/// Domains without an owner have nobody to remind and are left out.
+pub fn expiry_report(domains: Vec<DomainRecord>, window: ExpiryWindow) -> ExpiryReport {
+    let mut owners = BTreeMap::<String, OwnerExpiry>::new();
+    let (mut expiring_num, mut in_grace_num, mut expired_num) = (0, 0, 0);
+
+    for domain in domains {
+        let (Some(owner), Some(expire)) = (domain.owner, domain.expire) else {
+            continue;
+        };
+        let Some(status) = window.status(expire) else {
+            continue;
+        };
+
+        let entry = ExpiryEntry {
+            id: domain.id,
+            name: domain.name,
+            expire,
+            days_left: (expire - window.now).div_euclid(DAY),
+        };
+        let owner = owners.entry(owner).or_default();
+        match status {
+            ExpiryStatus::Expiring => {
+                expiring_num += 1;
+                owner.expiring.push(entry);
+            }
+            ExpiryStatus::InGrace => {
+                in_grace_num += 1;
+                owner.in_grace.push(entry);
+            }
+            ExpiryStatus::Expired => {
+                expired_num += 1;
+                owner.expired.push(entry);
+            }
+        }
+    }
+
+    for owner in owners.values_mut() {
+        owner.expiring.sort_by_key(|e| e.expire);
+        owner.in_grace.sort_by_key(|e| e.expire);
+        owner.expired.sort_by_key(|e| e.expire);
+    }
+
+    ExpiryReport {
+        now: window.now,
+        days: window.days,
+        grace_days: window.grace_days,
+        expiring_num,
+        in_grace_num,
+        expired_num,
+        owners,
+    }
+}
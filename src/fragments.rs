@@ -0,0 +1,94 @@
+//! Scalars and fragments shared by every query module.
+//!
+//! Query modules build their own operations on top of these with
+//! `#[cynic::schema_for_derives(file = r#"schema.gql"#, module = "schema")]`
+//! and `use crate::schema;`, so a `Domain` fetched by one module is the same
+//! Rust type as one fetched by another.
+#[cynic::schema_for_derives(file = r#"schema.gql"#, module = "schema")]
+mod queries {
+    use crate::schema;
+
+    #[derive(cynic::QueryFragment, Debug, Clone)]
+    #[cynic(graphql_type = "Account")]
+    pub struct AccountFields {
+        pub id: Bytes,
+    }
+
+    /// A domain selected by id only, for selections that need nothing else.
+    #[derive(cynic::QueryFragment, Debug, Clone)]
+    #[cynic(graphql_type = "Domain")]
+    pub struct DomainId {
+        pub id: Bytes,
+    }
+
+    #[derive(cynic::QueryFragment, Debug, Clone)]
+    #[cynic(graphql_type = "Domain")]
+    pub struct DomainFields {
+        pub id: Bytes,
+        pub name: Option<String>,
+        pub created_at: BigInt,
+    }
+
+    /// Pins a query to a block, see [`crate::BuildQuery::build_query`].
+    #[derive(cynic::InputObject, Debug, Clone)]
+    #[cynic(graphql_type = "Block_height")]
+    pub struct BlockHeight {
+        #[cynic(skip_serializing_if = "Option::is_none")]
+        pub hash: Option<Bytes>,
+        #[cynic(skip_serializing_if = "Option::is_none")]
+        pub number: Option<i32>,
+        #[cynic(rename = "number_gte", skip_serializing_if = "Option::is_none")]
+        pub number_gte: Option<i32>,
+    }
+
+    /// Matches domains by full name or owner. Also used as the nested
+    /// `domain_` filter of event queries.
+    #[derive(cynic::InputObject, Debug, Default, Clone)]
+    #[cynic(graphql_type = "Domain_filter")]
+    pub struct DomainFilter {
+        #[cynic(skip_serializing_if = "Option::is_none")]
+        pub name: Option<String>,
+        #[cynic(skip_serializing_if = "Option::is_none")]
+        pub owner: Option<String>,
+        #[cynic(rename = "_change_block", skip_serializing_if = "Option::is_none")]
+        pub change_block: Option<BlockChangedFilter>,
+    }
+
+    /// The `_change_block` filter: entities written at or after a block.
+    #[derive(cynic::InputObject, Debug, Clone)]
+    #[cynic(graphql_type = "BlockChangedFilter")]
+    pub struct BlockChangedFilter {
+        #[cynic(rename = "number_gte")]
+        pub number_gte: i32,
+    }
+
+    #[derive(cynic::Scalar, Debug, Clone)]
+    pub struct BigInt(pub String);
+
+    #[derive(cynic::Scalar, Debug, Clone)]
+    pub struct Bytes(pub String);
+}
+
+pub use queries::{
+    AccountFields, BigInt, BlockChangedFilter, BlockHeight, Bytes, DomainFields, DomainFilter,
+    DomainId,
+};
+
+impl BlockHeight {
+    pub fn number(number: i32) -> Self {
+        Self {
+            hash: None,
+            number: Some(number),
+            number_gte: None,
+        }
+    }
+}
+
+impl BlockChangedFilter {
+    /// Entities changed in any block after `block`.
+    pub fn after(block: i32) -> Self {
+        Self {
+            number_gte: block + 1,
+        }
+    }
+}
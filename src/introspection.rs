@@ -0,0 +1,915 @@
+/*!
+Schema introspection against the live subgraph.
+
+`pull` renders the introspection result as SDL in the same layout `gq --introspect`
+produces, `check` compares it with the checked-in `schema.gql` that every
+`cynic::schema_for_derives` in this crate is validated against.
+*/
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display, Write},
+};
+
+use anyhow::Context;
+use serde::Deserialize;
+
+const INTROSPECTION_QUERY: &str = r#"
+query IntrospectionQuery {
+  __schema {
+    queryType { name }
+    mutationType { name }
+    subscriptionType { name }
+    types { ...FullType }
+    directives {
+      name
+      description
+      locations
+      args { ...InputValue }
+    }
+  }
+}
+
+fragment FullType on __Type {
+  kind
+  name
+  description
+  fields(includeDeprecated: true) {
+    name
+    description
+    args { ...InputValue }
+    type { ...TypeRef }
+  }
+  inputFields { ...InputValue }
+  interfaces { ...TypeRef }
+  enumValues(includeDeprecated: true) {
+    name
+    description
+  }
+  possibleTypes { ...TypeRef }
+}
+
+fragment InputValue on __InputValue {
+  name
+  description
+  type { ...TypeRef }
+  defaultValue
+}
+
+fragment TypeRef on __Type {
+  kind
+  name
+  ofType {
+    kind
+    name
+    ofType {
+      kind
+      name
+      ofType {
+        kind
+        name
+        ofType {
+          kind
+          name
+        }
+      }
+    }
+  }
+}
+"#;
+
+/// The GraphQL documents at the top of the query modules, which their
+/// `cynic` derives follow. [`queried_types`] walks them.
+const QUERY_MODULES: &[(&str, &str)] = &[
+    ("account_profile", include_str!("account_profile.rs")),
+    ("accouts", include_str!("accouts.rs")),
+    ("approvals", include_str!("approvals.rs")),
+    ("domain_events", include_str!("domain_events.rs")),
+    ("domains", include_str!("domains.rs")),
+    ("meta", include_str!("meta.rs")),
+    ("new_accounts", include_str!("new_accounts.rs")),
+    ("new_subdomains", include_str!("new_subdomains.rs")),
+    ("prices", include_str!("prices.rs")),
+    (
+        "registration_events",
+        include_str!("registration_events.rs"),
+    ),
+    ("registrations", include_str!("registrations.rs")),
+    ("resolvers", include_str!("resolvers.rs")),
+    ("reverse_records", include_str!("reverse_records.rs")),
+    ("token_list", include_str!("token_list.rs")),
+    ("transfers", include_str!("transfers.rs")),
+];
+
+const BUILTIN_SCALARS: &[&str] = &["String", "Int", "Float", "Boolean", "ID"];
+const BUILTIN_DIRECTIVES: &[&str] = &["skip", "include", "deprecated", "specifiedBy"];
+
+#[derive(Debug, Deserialize)]
+struct Response {
+    data: Option<Data>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Data {
+    #[serde(rename = "__schema")]
+    schema: Schema,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Schema {
+    query_type: Option<Named>,
+    mutation_type: Option<Named>,
+    subscription_type: Option<Named>,
+    types: Vec<FullType>,
+    #[serde(default)]
+    directives: Vec<Directive>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Named {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FullType {
+    kind: String,
+    name: String,
+    description: Option<String>,
+    fields: Option<Vec<Field>>,
+    input_fields: Option<Vec<InputValue>>,
+    interfaces: Option<Vec<TypeRef>>,
+    enum_values: Option<Vec<EnumValue>>,
+    possible_types: Option<Vec<TypeRef>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Field {
+    name: String,
+    description: Option<String>,
+    args: Vec<InputValue>,
+    #[serde(rename = "type")]
+    ty: TypeRef,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InputValue {
+    name: String,
+    description: Option<String>,
+    #[serde(rename = "type")]
+    ty: TypeRef,
+    default_value: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EnumValue {
+    name: String,
+    description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Directive {
+    name: String,
+    description: Option<String>,
+    locations: Vec<String>,
+    args: Vec<InputValue>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TypeRef {
+    kind: String,
+    name: Option<String>,
+    of_type: Option<Box<TypeRef>>,
+}
+
+impl Display for TypeRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.kind.as_str(), &self.of_type) {
+            ("NON_NULL", Some(inner)) => write!(f, "{inner}!"),
+            ("LIST", Some(inner)) => write!(f, "[{inner}]"),
+            _ => f.write_str(self.name.as_deref().unwrap_or_default()),
+        }
+    }
+}
+
+/// Run the introspection query through [`crate::client`].
+pub async fn introspect() -> anyhow::Result<Schema> {
+    let response = crate::client()
+        .json(&serde_json::json!({ "query": INTROSPECTION_QUERY }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<Response>()
+        .await?;
+
+    Ok(response
+        .data
+        .context("introspection returned no data")?
+        .schema)
+}
+
+impl Schema {
+    pub fn to_sdl(&self) -> String {
+        let mut blocks = Vec::new();
+
+        for directive in self
+            .directives
+            .iter()
+            .filter(|d| !BUILTIN_DIRECTIVES.contains(&d.name.as_str()))
+        {
+            let mut block = description(directive.description.as_deref(), "");
+            write!(block, "directive @{}", directive.name).unwrap();
+            block.push_str(&arguments(&directive.args, ""));
+            write!(block, " on {}", directive.locations.join(" | ")).unwrap();
+            blocks.push(block);
+        }
+
+        if !self.is_default_root() {
+            let mut block = String::from("schema {\n");
+            for (operation, root) in [
+                ("query", &self.query_type),
+                ("mutation", &self.mutation_type),
+                ("subscription", &self.subscription_type),
+            ] {
+                if let Some(root) = root {
+                    writeln!(block, "  {operation}: {}", root.name).unwrap();
+                }
+            }
+            block.push('}');
+            blocks.push(block);
+        }
+
+        let types = self
+            .types
+            .iter()
+            .filter(|t| !t.name.starts_with("__") && !BUILTIN_SCALARS.contains(&t.name.as_str()));
+        for ty in types {
+            blocks.push(ty.to_sdl());
+        }
+
+        blocks.join("\n\n") + "\n"
+    }
+
+    fn is_default_root(&self) -> bool {
+        let is =
+            |named: &Option<Named>, default: &str| named.as_ref().is_none_or(|n| n.name == default);
+        is(&self.query_type, "Query")
+            && is(&self.mutation_type, "Mutation")
+            && is(&self.subscription_type, "Subscription")
+    }
+
+    /// Flatten into the shape used for drift detection.
+    pub fn shapes(&self) -> BTreeMap<String, TypeShape> {
+        self.types
+            .iter()
+            .filter(|t| !t.name.starts_with("__") && !BUILTIN_SCALARS.contains(&t.name.as_str()))
+            .map(|t| {
+                let mut members = BTreeMap::new();
+                for field in t.fields.iter().flatten() {
+                    members.insert(field.name.clone(), field.ty.to_string());
+                    for arg in field.args.iter() {
+                        members.insert(format!("{}({})", field.name, arg.name), arg.ty.to_string());
+                    }
+                }
+                for field in t.input_fields.iter().flatten() {
+                    members.insert(field.name.clone(), field.ty.to_string());
+                }
+                for value in t.enum_values.iter().flatten() {
+                    members.insert(value.name.clone(), String::new());
+                }
+                for possible in t.possible_types.iter().flatten() {
+                    if t.kind == "UNION" {
+                        members.insert(possible.to_string(), String::new());
+                    }
+                }
+                for interface in t.interfaces.iter().flatten() {
+                    members.insert(format!("implements {interface}"), String::new());
+                }
+
+                (
+                    t.name.clone(),
+                    TypeShape {
+                        kind: t.kind.clone(),
+                        members,
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+impl FullType {
+    fn to_sdl(&self) -> String {
+        let mut block = description(self.description.as_deref(), "");
+
+        match self.kind.as_str() {
+            "SCALAR" => write!(block, "scalar {}", self.name).unwrap(),
+            "UNION" => {
+                let members = self
+                    .possible_types
+                    .iter()
+                    .flatten()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>();
+                write!(block, "union {} = {}", self.name, members.join(" | ")).unwrap();
+            }
+            "ENUM" => {
+                writeln!(block, "enum {} {{", self.name).unwrap();
+                let values = self.enum_values.iter().flatten().enumerate();
+                for (i, value) in values {
+                    if i > 0 && value.description.is_some() {
+                        block.push('\n');
+                    }
+                    block.push_str(&description(value.description.as_deref(), "  "));
+                    writeln!(block, "  {}", value.name).unwrap();
+                }
+                block.push('}');
+            }
+            "INPUT_OBJECT" => {
+                writeln!(block, "input {} {{", self.name).unwrap();
+                let fields = self.input_fields.iter().flatten().enumerate();
+                for (i, field) in fields {
+                    if i > 0 && field.description.is_some() {
+                        block.push('\n');
+                    }
+                    writeln!(block, "{}", input_value(field, "  ")).unwrap();
+                }
+                block.push('}');
+            }
+            kind => {
+                let keyword = if kind == "INTERFACE" {
+                    "interface"
+                } else {
+                    "type"
+                };
+                write!(block, "{keyword} {}", self.name).unwrap();
+                let interfaces = self
+                    .interfaces
+                    .iter()
+                    .flatten()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>();
+                if !interfaces.is_empty() {
+                    write!(block, " implements {}", interfaces.join(" & ")).unwrap();
+                }
+                block.push_str(" {\n");
+                let fields = self.fields.iter().flatten().enumerate();
+                for (i, field) in fields {
+                    if i > 0 && field.description.is_some() {
+                        block.push('\n');
+                    }
+                    block.push_str(&description(field.description.as_deref(), "  "));
+                    writeln!(
+                        block,
+                        "  {}{}: {}",
+                        field.name,
+                        arguments(&field.args, "  "),
+                        field.ty
+                    )
+                    .unwrap();
+                }
+                block.push('}');
+            }
+        }
+
+        block
+    }
+}
+
+fn description(description: Option<&str>, indent: &str) -> String {
+    match description {
+        Some(d) if !d.contains('\n') && d.len() < 70 => format!("{indent}\"\"\"{d}\"\"\"\n"),
+        Some(d) => {
+            let lines = d
+                .lines()
+                .map(|l| format!("{indent}{l}"))
+                .collect::<Vec<_>>()
+                .join("\n");
+            format!("{indent}\"\"\"\n{lines}\n{indent}\"\"\"\n")
+        }
+        None => String::new(),
+    }
+}
+
+fn input_value(value: &InputValue, indent: &str) -> String {
+    let mut res = description(value.description.as_deref(), indent);
+    write!(res, "{indent}{}: {}", value.name, value.ty).unwrap();
+    if let Some(default) = &value.default_value {
+        write!(res, " = {default}").unwrap();
+    }
+    res
+}
+
+fn arguments(args: &[InputValue], indent: &str) -> String {
+    if args.is_empty() {
+        return String::new();
+    }
+
+    if args.iter().all(|a| a.description.is_none()) {
+        let args = args.iter().map(|a| input_value(a, "")).collect::<Vec<_>>();
+        return format!("({})", args.join(", "));
+    }
+
+    let inner = format!("{indent}  ");
+    let args = args
+        .iter()
+        .map(|a| input_value(a, &inner))
+        .collect::<Vec<_>>();
+    format!("(\n{}\n{indent})", args.join("\n\n"))
+}
+
+/// A type reduced to its kind and `member -> type` pairs. Field arguments are
+/// keyed as `field(arg)`, enum values and union members map to an empty string.
+#[derive(Debug, PartialEq, Eq)]
+pub struct TypeShape {
+    pub kind: String,
+    pub members: BTreeMap<String, String>,
+}
+
+/// Parse a checked-in SDL document into the same shape [`Schema::shapes`] yields.
+pub fn parse_shapes(sdl: &str) -> anyhow::Result<BTreeMap<String, TypeShape>> {
+    use graphql_parser::schema::{Definition, TypeDefinition};
+
+    let document = graphql_parser::parse_schema::<String>(sdl)?;
+    let mut shapes = BTreeMap::new();
+
+    for definition in document.definitions {
+        let Definition::TypeDefinition(definition) = definition else {
+            continue;
+        };
+        let mut members = BTreeMap::new();
+
+        let (name, kind) = match definition {
+            TypeDefinition::Scalar(t) => (t.name, "SCALAR"),
+            TypeDefinition::Object(t) => {
+                for field in t.fields {
+                    for arg in field.arguments {
+                        members.insert(
+                            format!("{}({})", field.name, arg.name),
+                            arg.value_type.to_string(),
+                        );
+                    }
+                    members.insert(field.name, field.field_type.to_string());
+                }
+                for interface in t.implements_interfaces {
+                    members.insert(format!("implements {interface}"), String::new());
+                }
+                (t.name, "OBJECT")
+            }
+            TypeDefinition::Interface(t) => {
+                for field in t.fields {
+                    for arg in field.arguments {
+                        members.insert(
+                            format!("{}({})", field.name, arg.name),
+                            arg.value_type.to_string(),
+                        );
+                    }
+                    members.insert(field.name, field.field_type.to_string());
+                }
+                for interface in t.implements_interfaces {
+                    members.insert(format!("implements {interface}"), String::new());
+                }
+                (t.name, "INTERFACE")
+            }
+            TypeDefinition::Union(t) => {
+                members.extend(t.types.into_iter().map(|t| (t, String::new())));
+                (t.name, "UNION")
+            }
+            TypeDefinition::Enum(t) => {
+                members.extend(t.values.into_iter().map(|v| (v.name, String::new())));
+                (t.name, "ENUM")
+            }
+            TypeDefinition::InputObject(t) => {
+                members.extend(
+                    t.fields
+                        .into_iter()
+                        .map(|f| (f.name, f.value_type.to_string())),
+                );
+                (t.name, "INPUT_OBJECT")
+            }
+        };
+
+        shapes.insert(
+            name,
+            TypeShape {
+                kind: kind.into(),
+                members,
+            },
+        );
+    }
+
+    Ok(shapes)
+}
+
+#[derive(Debug)]
+pub enum Drift {
+    TypeAdded {
+        ty: String,
+    },
+    TypeRemoved {
+        ty: String,
+    },
+    KindChanged {
+        ty: String,
+        old: String,
+        new: String,
+    },
+    MemberAdded {
+        ty: String,
+        member: String,
+    },
+    MemberRemoved {
+        ty: String,
+        member: String,
+    },
+    MemberChanged {
+        ty: String,
+        member: String,
+        old: String,
+        new: String,
+    },
+}
+
+impl Drift {
+    pub fn ty(&self) -> &str {
+        match self {
+            Drift::TypeAdded { ty }
+            | Drift::TypeRemoved { ty }
+            | Drift::KindChanged { ty, .. }
+            | Drift::MemberAdded { ty, .. }
+            | Drift::MemberRemoved { ty, .. }
+            | Drift::MemberChanged { ty, .. } => ty,
+        }
+    }
+
+    /// Removals and changes can break an existing query, additions cannot.
+    pub fn is_breaking(&self) -> bool {
+        !matches!(self, Drift::TypeAdded { .. } | Drift::MemberAdded { .. })
+    }
+
+    /// Whether the type is one of `queried`, see [`queried_types`].
+    pub fn is_queried(&self, queried: &BTreeSet<String>) -> bool {
+        queried.contains(self.ty())
+    }
+}
+
+impl Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Drift::TypeAdded { ty } => write!(f, "+ type {ty}"),
+            Drift::TypeRemoved { ty } => write!(f, "- type {ty}"),
+            Drift::KindChanged { ty, old, new } => write!(f, "~ type {ty}: {old} -> {new}"),
+            Drift::MemberAdded { ty, member } => write!(f, "+ {ty}.{member}"),
+            Drift::MemberRemoved { ty, member } => write!(f, "- {ty}.{member}"),
+            Drift::MemberChanged {
+                ty,
+                member,
+                old,
+                new,
+            } => write!(f, "~ {ty}.{member}: {old} -> {new}"),
+        }
+    }
+}
+
+/// Compare the checked-in shapes (`local`) with the live ones (`live`).
+pub fn diff(local: &BTreeMap<String, TypeShape>, live: &BTreeMap<String, TypeShape>) -> Vec<Drift> {
+    let mut drift = Vec::new();
+    let names = local.keys().chain(live.keys()).collect::<BTreeSet<_>>();
+
+    for name in names {
+        let ty = name.clone();
+        match (local.get(name), live.get(name)) {
+            (Some(_), None) => drift.push(Drift::TypeRemoved { ty }),
+            (None, Some(_)) => drift.push(Drift::TypeAdded { ty }),
+            (Some(old), Some(new)) if old.kind != new.kind => drift.push(Drift::KindChanged {
+                ty,
+                old: old.kind.clone(),
+                new: new.kind.clone(),
+            }),
+            (Some(old), Some(new)) => {
+                let members = old
+                    .members
+                    .keys()
+                    .chain(new.members.keys())
+                    .collect::<BTreeSet<_>>();
+                for member in members {
+                    let ty = ty.clone();
+                    let m = member.clone();
+                    match (old.members.get(member), new.members.get(member)) {
+                        (Some(_), None) => drift.push(Drift::MemberRemoved { ty, member: m }),
+                        (None, Some(_)) => drift.push(Drift::MemberAdded { ty, member: m }),
+                        (Some(o), Some(n)) if o != n => drift.push(Drift::MemberChanged {
+                            ty,
+                            member: m,
+                            old: o.clone(),
+                            new: n.clone(),
+                        }),
+                        _ => {}
+                    }
+                }
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+
+    drift
+}
+
+/// Types touched by the operations in this crate, found by walking their
+/// documents against `schema`: the types of the selected fields, inline
+/// fragments and fragments, and of the arguments and variables passed, with
+/// the input objects and enums those hold. Nested `*_filter` inputs only count
+/// when an operation passes them itself. Drift in any other type is reported
+/// but does not fail `check`.
+///
+/// Fails on a field or argument `schema` doesn't have, which means a
+/// document no longer matches it.
+pub fn queried_types(schema: &BTreeMap<String, TypeShape>) -> anyhow::Result<BTreeSet<String>> {
+    use graphql_parser::query::{Definition, OperationDefinition};
+
+    let mut walk = Walk {
+        schema,
+        types: BTreeSet::new(),
+    };
+
+    for (module, source) in QUERY_MODULES {
+        let document =
+            query_document(source).with_context(|| format!("{module} has no query document"))?;
+        let document = graphql_parser::parse_query::<String>(document)
+            .with_context(|| format!("parsing the query document of {module}"))?;
+
+        let fragments = document
+            .definitions
+            .iter()
+            .filter_map(|definition| match definition {
+                Definition::Fragment(fragment) => Some(fragment),
+                Definition::Operation(_) => None,
+            })
+            .collect::<Vec<_>>();
+
+        for definition in document.definitions.iter() {
+            let (root, variables, selection_set) = match definition {
+                Definition::Operation(OperationDefinition::Query(q)) => {
+                    ("Query", &q.variable_definitions[..], &q.selection_set)
+                }
+                Definition::Operation(OperationDefinition::SelectionSet(set)) => {
+                    ("Query", &[][..], set)
+                }
+                Definition::Operation(_) => anyhow::bail!("{module} has a non-query operation"),
+                Definition::Fragment(fragment) => {
+                    let graphql_parser::query::TypeCondition::On(on) = &fragment.type_condition;
+                    (on.as_str(), &[][..], &fragment.selection_set)
+                }
+            };
+
+            for variable in variables {
+                walk.input(named_type(&variable.var_type.to_string()));
+            }
+            walk.selection_set(root, selection_set, &fragments)
+                .with_context(|| format!("in the query document of {module}"))?;
+        }
+    }
+
+    Ok(walk.types)
+}
+
+/// The first fenced block of a module, its query document.
+fn query_document(source: &str) -> Option<&str> {
+    let start = source.find("```\n")? + 4;
+    let len = source[start..].find("```")?;
+    Some(&source[start..start + len])
+}
+
+/// `Domain` of `[Domain!]!`.
+fn named_type(ty: &str) -> &str {
+    ty.trim_matches(|c| matches!(c, '[' | ']' | '!'))
+}
+
+struct Walk<'a> {
+    schema: &'a BTreeMap<String, TypeShape>,
+    types: BTreeSet<String>,
+}
+
+impl Walk<'_> {
+    fn selection_set(
+        &mut self,
+        on: &str,
+        selection_set: &graphql_parser::query::SelectionSet<'_, String>,
+        fragments: &[&graphql_parser::query::FragmentDefinition<'_, String>],
+    ) -> anyhow::Result<()> {
+        use graphql_parser::query::{Selection, TypeCondition};
+
+        let shape = self
+            .schema
+            .get(on)
+            .with_context(|| format!("unknown type {on}"))?;
+        self.types.insert(on.to_string());
+
+        for selection in selection_set.items.iter() {
+            match selection {
+                Selection::Field(field) if field.name.starts_with("__") => {}
+                Selection::Field(field) => {
+                    let ty = shape
+                        .members
+                        .get(&field.name)
+                        .with_context(|| format!("unknown field {on}.{}", field.name))?;
+                    for (arg, _) in field.arguments.iter() {
+                        let arg_ty = shape
+                            .members
+                            .get(&format!("{}({arg})", field.name))
+                            .with_context(|| {
+                                format!("unknown argument {on}.{}({arg})", field.name)
+                            })?;
+                        self.input(named_type(arg_ty));
+                    }
+
+                    let ty = named_type(ty);
+                    if field.selection_set.items.is_empty() {
+                        self.leaf(ty);
+                    } else {
+                        self.selection_set(ty, &field.selection_set, fragments)?;
+                    }
+                }
+                Selection::InlineFragment(fragment) => {
+                    let on = match &fragment.type_condition {
+                        Some(TypeCondition::On(ty)) => ty.as_str(),
+                        None => on,
+                    };
+                    self.selection_set(on, &fragment.selection_set, fragments)?;
+                }
+                Selection::FragmentSpread(spread) => {
+                    let fragment = fragments
+                        .iter()
+                        .find(|f| f.name == spread.fragment_name)
+                        .with_context(|| format!("unknown fragment {}", spread.fragment_name))?;
+                    let TypeCondition::On(ty) = &fragment.type_condition;
+                    self.selection_set(ty, &fragment.selection_set, fragments)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// A scalar or enum, builtin scalars are not in the schema.
+    fn leaf(&mut self, ty: &str) {
+        if self.schema.contains_key(ty) {
+            self.types.insert(ty.to_string());
+        }
+    }
+
+    /// An argument or variable type, with the input objects and enums its
+    /// fields hold except nested filters.
+    fn input(&mut self, ty: &str) {
+        let Some(shape) = self.schema.get(ty) else {
+            return;
+        };
+        if !self.types.insert(ty.to_string()) || shape.kind != "INPUT_OBJECT" {
+            return;
+        }
+        for member in shape.members.values() {
+            let member = named_type(member);
+            if !member.ends_with("_filter") {
+                self.input(member);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCAL: &str = r#"
+scalar BigInt
+
+interface Event {
+  id: ID!
+}
+
+type Query {
+  domains(skip: Int = 0, where: Domain_filter): [Domain!]!
+}
+
+type Domain implements Event {
+  id: ID!
+  name: String
+  expiry: BigInt
+}
+
+enum OrderDirection {
+  asc
+  desc
+}
+
+input Domain_filter {
+  name: String
+}
+
+union Item = Domain
+"#;
+
+    const LIVE: &str = r#"
+scalar BigInt
+
+type Event {
+  id: ID!
+}
+
+type Query {
+  domains(skip: Int = 0, where: Domain_filter, first: Int): [Domain!]!
+}
+
+type Domain implements Event {
+  id: ID!
+  name: String!
+}
+
+enum OrderDirection {
+  asc
+  desc
+}
+
+input Domain_filter {
+  name: String
+  name_in: [String!]
+}
+
+type Account {
+  id: ID!
+}
+"#;
+
+    #[test]
+    fn parse_shapes_flattens_every_kind() {
+        let shapes = parse_shapes(LOCAL).unwrap();
+
+        let query = &shapes["Query"];
+        assert_eq!(query.kind, "OBJECT");
+        assert_eq!(query.members["domains"], "[Domain!]!");
+        assert_eq!(query.members["domains(skip)"], "Int");
+        assert_eq!(query.members["domains(where)"], "Domain_filter");
+
+        let domain = &shapes["Domain"];
+        assert_eq!(domain.members["name"], "String");
+        assert_eq!(domain.members["implements Event"], "");
+
+        assert_eq!(shapes["Event"].kind, "INTERFACE");
+        assert_eq!(shapes["BigInt"].kind, "SCALAR");
+        assert_eq!(shapes["OrderDirection"].members.len(), 2);
+        assert_eq!(shapes["Domain_filter"].kind, "INPUT_OBJECT");
+        assert_eq!(shapes["Domain_filter"].members["name"], "String");
+        assert_eq!(shapes["Item"].members["Domain"], "");
+    }
+
+    #[test]
+    fn diff_reports_additions_removals_and_changes() {
+        let drift = diff(&parse_shapes(LOCAL).unwrap(), &parse_shapes(LIVE).unwrap())
+            .iter()
+            .map(|d| (d.to_string(), d.is_breaking()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            drift,
+            [
+                ("+ type Account".to_string(), false),
+                ("- Domain.expiry".to_string(), true),
+                ("~ Domain.name: String -> String!".to_string(), true),
+                ("+ Domain_filter.name_in".to_string(), false),
+                ("~ type Event: INTERFACE -> OBJECT".to_string(), true),
+                ("- type Item".to_string(), true),
+                ("+ Query.domains(first)".to_string(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn query_documents_match_the_checked_in_schema() {
+        let local = parse_shapes(include_str!("../schema.gql")).unwrap();
+        let queried = queried_types(&local).unwrap();
+
+        for ty in [
+            "Query",
+            "Domain",
+            "Domain_filter",
+            "BlockChangedFilter",
+            "Block_height",
+            "OrderDirection",
+            "Transfer_orderBy",
+            "RegistrationEvent_orderBy",
+            "DomainEvent_filter",
+            "NewResolver",
+            "_Meta_",
+        ] {
+            assert!(queried.contains(ty), "{ty} is not queried");
+        }
+
+        let drift = Drift::MemberRemoved {
+            ty: "Transfer_orderBy".into(),
+            member: "blockNumber".into(),
+        };
+        assert!(drift.is_queried(&queried));
+    }
+}
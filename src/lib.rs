@@ -2,17 +2,54 @@
 #![feature(return_position_impl_trait_in_trait)]
 #![feature(async_fn_in_trait)]
 
+pub mod account_profile;
 pub mod accouts;
+pub mod approvals;
+pub mod cache;
+pub mod capacity;
+pub mod columnar;
+pub mod domain_events;
+pub mod domains;
+pub mod envelope;
+pub mod expiry;
+pub mod fragments;
+pub mod introspection;
+pub mod meta;
+pub mod mirror;
 pub mod new_accounts;
 pub mod new_subdomains;
+pub mod output;
+pub mod prices;
+pub mod registration_events;
 pub mod registrations;
+pub mod resolvers;
+pub mod revenue;
+pub mod reverse_records;
+pub mod snapshot;
+pub mod subdomain_tree;
 pub mod token_list;
+pub mod transfers;
+
+#[allow(non_snake_case, non_camel_case_types)]
+pub mod schema {
+    cynic::use_schema!(r#"schema.gql"#);
+}
 
 // use std::collections::HashMap;
 
 use cynic::Operation;
 use futures_util::Stream;
 
+pub const ENDPOINT: &str = "https://pns-graph.ddns.so/subgraphs/name/graphprotocol/pns";
+
+/// Request against the PNS subgraph, shared by cynic operations and raw
+/// queries such as schema introspection.
+pub fn client() -> reqwest::RequestBuilder {
+    reqwest::Client::new().post(ENDPOINT)
+}
+
+/// Run `query`, answering from the [`cache`] when it is installed and the
+/// query is pinned to a block.
 pub async fn run_graphql<ResponseData, Vars>(
     query: Operation<ResponseData, Vars>,
 ) -> cynic::GraphQlResponse<ResponseData>
@@ -20,19 +57,35 @@ where
     Vars: serde::Serialize,
     ResponseData: serde::de::DeserializeOwned + 'static,
 {
-    cynic::http::ReqwestExt::run_graphql(
-        reqwest::Client::new().post("https://pns-graph.ddns.so/subgraphs/name/graphprotocol/pns"),
-        query,
-    )
-    .await
-    .unwrap()
+    if let Some(body) = cache::get(&query.query, &query.variables).await {
+        if let Ok(res) = serde_json::from_slice(&body) {
+            return res;
+        }
+    }
+
+    let body = client()
+        .json(&query)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+    let res: cynic::GraphQlResponse<ResponseData> = serde_json::from_slice(&body).unwrap();
+    if res.errors.is_none() && res.data.is_some() {
+        cache::put(&query.query, &query.variables, &body).await;
+    }
+    res
 }
 
 pub trait BuildQuery {
     type Vars: serde::Serialize;
     type ResponseData: serde::de::DeserializeOwned + 'static + IsFull;
 
-    fn build_query(offset: i32) -> Operation<Self::ResponseData, Self::Vars>;
+    /// `block` pins every page to the same block so pagination sees one
+    /// consistent snapshot.
+    fn build_query(offset: i32, block: i32) -> Operation<Self::ResponseData, Self::Vars>;
 }
 
 pub trait IsFull {
@@ -56,29 +109,85 @@ pub trait IsFullAsync {
         self.len() == 1000
     }
 
-    async fn into_stream(self) -> impl Stream<Item = Self::Item>;
+    /// `block` is the block the page was queried at, for any query the
+    /// items need to be completed.
+    async fn into_stream(self, block: i32) -> impl Stream<Item = Self::Item>;
 }
 
+/// Query every page at the subgraph's current block.
 pub async fn query_all<QueryBuilder>(
 ) -> Vec<<<QueryBuilder as BuildQuery>::ResponseData as IsFull>::Item>
 where
     QueryBuilder: BuildQuery,
+{
+    query_all_at::<QueryBuilder>(meta::query_meta().await.block_number).await
+}
+
+/// Query every page at `block`.
+pub async fn query_all_at<QueryBuilder>(
+    block: i32,
+) -> Vec<<<QueryBuilder as BuildQuery>::ResponseData as IsFull>::Item>
+where
+    QueryBuilder: BuildQuery,
+{
+    query_pages(|offset| QueryBuilder::build_query(offset, block)).await
+}
+
+/// Like [`query_all_at`] but hands every item to `on_item` as its page
+/// arrives instead of collecting them.
+pub async fn query_all_at_each<QueryBuilder>(
+    block: i32,
+    on_item: impl FnMut(
+        <<QueryBuilder as BuildQuery>::ResponseData as IsFull>::Item,
+    ) -> anyhow::Result<()>,
+) -> anyhow::Result<()>
+where
+    QueryBuilder: BuildQuery,
+{
+    query_pages_each(|offset| QueryBuilder::build_query(offset, block), on_item).await
+}
+
+/// Run `build_query` for offsets 0, 1000, ... until a page comes back short.
+/// For builders that need more than an offset and a block, such as filters.
+pub async fn query_pages<ResponseData, Vars>(
+    build_query: impl Fn(i32) -> Operation<ResponseData, Vars>,
+) -> Vec<<ResponseData as IsFull>::Item>
+where
+    Vars: serde::Serialize,
+    ResponseData: serde::de::DeserializeOwned + 'static + IsFull,
+{
+    let mut res = Vec::new();
+    query_pages_each(build_query, |item| {
+        res.push(item);
+        Ok(())
+    })
+    .await
+    .expect("collecting pages does not fail");
+    res
+}
+
+/// [`query_pages`] without collecting, stops at the first error of `on_item`.
+pub async fn query_pages_each<ResponseData, Vars>(
+    build_query: impl Fn(i32) -> Operation<ResponseData, Vars>,
+    mut on_item: impl FnMut(<ResponseData as IsFull>::Item) -> anyhow::Result<()>,
+) -> anyhow::Result<()>
+where
+    Vars: serde::Serialize,
+    ResponseData: serde::de::DeserializeOwned + 'static + IsFull,
 {
     let mut is_full = true;
     let mut offset = 0;
-    let mut res = Vec::new();
 
     while is_full {
-        let data = run_graphql(QueryBuilder::build_query(offset))
-            .await
-            .data
-            .unwrap();
+        let data = run_graphql(build_query(offset)).await.data.unwrap();
         offset += 1000;
         is_full = data.is_full();
-        res.extend(data.into_iter());
+        for item in data.into_iter() {
+            on_item(item)?;
+        }
     }
 
-    res
+    Ok(())
 }
 
 pub trait HandleId {
@@ -97,6 +206,17 @@ impl HandleId for String {
     }
 }
 
+/// Sort key of an event: its block, then its place in the block. Event ids
+/// end in `-{logIndex}`; ids that don't sort after those of the same block,
+/// by the id itself.
+pub fn event_order(block_number: i32, id: &str) -> (i32, u64, &str) {
+    let log_index = id
+        .rsplit_once('-')
+        .and_then(|(_, index)| index.parse().ok())
+        .unwrap_or(u64::MAX);
+    (block_number, log_index, id)
+}
+
 pub const ACCOUNT_ID_LEN: usize = 42;
 pub const DOMAIN_ID_LEN: usize = 66;
 
@@ -0,0 +1,132 @@
+/*!
+```
+query QueryMeta {
+  _meta {
+    deployment
+    hasIndexingErrors
+    block {
+      number
+      hash
+      timestamp
+    }
+  }
+}
+
+query QueryMetaAt($block: Block_height) {
+  _meta(block: $block) {
+    deployment
+    hasIndexingErrors
+    block {
+      number
+      hash
+      timestamp
+    }
+  }
+}
+```
+*/
+#[cynic::schema_for_derives(file = r#"schema.gql"#, module = "schema")]
+mod queries {
+    use crate::{
+        fragments::{BlockHeight, Bytes},
+        schema,
+    };
+
+    #[derive(cynic::QueryVariables, Debug)]
+    pub struct QueryMetaAtVariables {
+        pub block: Option<BlockHeight>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    #[cynic(graphql_type = "Query")]
+    pub struct QueryMeta {
+        #[cynic(rename = "_meta")]
+        pub meta: Option<Meta>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    #[cynic(graphql_type = "Query", variables = "QueryMetaAtVariables")]
+    pub struct QueryMetaAt {
+        #[cynic(rename = "_meta")]
+        #[arguments(block: $block)]
+        pub meta: Option<Meta>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    #[cynic(graphql_type = "_Meta_")]
+    pub struct Meta {
+        pub deployment: String,
+        pub has_indexing_errors: bool,
+        pub block: Block,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    #[cynic(graphql_type = "_Block_")]
+    pub struct Block {
+        pub number: i32,
+        pub hash: Option<Bytes>,
+        pub timestamp: Option<i32>,
+    }
+}
+
+use serde::{Deserialize, Serialize};
+
+use crate::{cache, fragments::BlockHeight, run_graphql};
+
+/// Subgraph deployment and the block it has indexed up to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Meta {
+    pub deployment: String,
+    pub block_number: i32,
+    pub block_hash: Option<String>,
+    pub block_timestamp: Option<i32>,
+    pub has_indexing_errors: bool,
+}
+
+/// The subgraph's latest block, which the [`cache`] also learns its
+/// deployment and head from.
+pub async fn query_meta() -> Meta {
+    let meta = run_graphql(<queries::QueryMeta as cynic::QueryBuilder>::build(()))
+        .await
+        .data
+        .unwrap()
+        .meta
+        .unwrap()
+        .into();
+
+    cache::set_head(&meta);
+    meta
+}
+
+/// Deployment and block details at `block`, which must already be indexed.
+pub async fn query_meta_at(block: i32) -> anyhow::Result<Meta> {
+    let res = run_graphql(<queries::QueryMetaAt as cynic::QueryBuilder>::build(
+        queries::QueryMetaAtVariables {
+            block: Some(BlockHeight::number(block)),
+        },
+    ))
+    .await;
+
+    if let Some(errors) = res.errors {
+        let messages = errors.into_iter().map(|e| e.message).collect::<Vec<_>>();
+        anyhow::bail!("no meta at block {block}: {}", messages.join(", "));
+    }
+
+    res.data
+        .and_then(|data| data.meta)
+        .map(Meta::from)
+        .ok_or_else(|| anyhow::anyhow!("no meta at block {block}"))
+}
+
+impl From<queries::Meta> for Meta {
+    fn from(meta: queries::Meta) -> Self {
+        Self {
+            deployment: meta.deployment,
+            block_number: meta.block.number,
+            block_hash: meta.block.hash.map(|hash| hash.0),
+            block_timestamp: meta.block.timestamp,
+            has_indexing_errors: meta.has_indexing_errors,
+        }
+    }
+}
@@ -0,0 +1,508 @@
+//! Postgres mirror of domains, accounts, registrations and events.
+//!
+//! Each sync upserts the entities changed and the events emitted after the
+//! last synced block, as of the target block, in one transaction, then moves
+//! `sync_state` forward. The first sync fetches everything. Entities removed
+//! from the subgraph are not noticed. An interrupted sync leaves the mirror
+//! at the previous block and is simply retried. A mirror only follows the
+//! deployment it was first synced from, a redeployment needs a new one.
+use std::collections::BTreeSet;
+
+use tokio_postgres::{Client, NoTls, Transaction};
+
+use crate::{
+    domain_events::{DomainEventRecord, DomainEventsBuilder},
+    domains::{DomainRecord, DomainsBuilder},
+    meta::Meta,
+    query_all_at,
+    registration_events::{RegistrationEventRecord, RegistrationEventsBuilder},
+    registrations::{Records, RecordsBuilder},
+    transfers::{TransferFilter, TransferRecord, TransfersBuilder},
+};
+
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS sync_state (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    block_number INTEGER NOT NULL,
+    block_hash TEXT,
+    deployment TEXT NOT NULL,
+    synced_at BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS accounts (
+    id TEXT PRIMARY KEY,
+    synced_block INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS domains (
+    id TEXT PRIMARY KEY,
+    name TEXT,
+    label_name TEXT,
+    labelhash TEXT,
+    parent TEXT,
+    owner TEXT,
+    resolved_address TEXT,
+    resolver TEXT,
+    subdomain_count INTEGER NOT NULL,
+    created_at BIGINT NOT NULL,
+    expire BIGINT,
+    capacity BIGINT,
+    synced_block INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS domains_name ON domains (name);
+CREATE INDEX IF NOT EXISTS domains_parent ON domains (parent);
+CREATE INDEX IF NOT EXISTS domains_owner ON domains (owner);
+
+CREATE TABLE IF NOT EXISTS registrations (
+    domain TEXT PRIMARY KEY,
+    origin TEXT NOT NULL,
+    expire BIGINT,
+    capacity BIGINT NOT NULL,
+    children INTEGER NOT NULL,
+    synced_block INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS registrations_origin ON registrations (origin);
+
+CREATE TABLE IF NOT EXISTS registration_events (
+    id TEXT PRIMARY KEY,
+    kind TEXT NOT NULL,
+    domain TEXT NOT NULL,
+    registrant TEXT NOT NULL,
+    block_number INTEGER NOT NULL,
+    triggered_date BIGINT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    cost NUMERIC(78, 0),
+    expiry BIGINT,
+    capacity BIGINT
+);
+CREATE INDEX IF NOT EXISTS registration_events_domain ON registration_events (domain);
+CREATE INDEX IF NOT EXISTS registration_events_block ON registration_events (block_number);
+
+CREATE TABLE IF NOT EXISTS transfers (
+    id TEXT PRIMARY KEY,
+    domain TEXT NOT NULL,
+    from_account TEXT,
+    to_account TEXT NOT NULL,
+    block_number INTEGER NOT NULL,
+    triggered_date BIGINT NOT NULL,
+    transaction_id TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS transfers_domain ON transfers (domain);
+CREATE INDEX IF NOT EXISTS transfers_block ON transfers (block_number);
+
+CREATE TABLE IF NOT EXISTS domain_events (
+    id TEXT PRIMARY KEY,
+    kind TEXT NOT NULL,
+    domain TEXT NOT NULL,
+    block_number INTEGER NOT NULL,
+    triggered_date BIGINT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    event JSONB NOT NULL
+);
+CREATE INDEX IF NOT EXISTS domain_events_domain ON domain_events (domain);
+CREATE INDEX IF NOT EXISTS domain_events_block ON domain_events (block_number);
+"#;
+
+/// Everything one sync writes: state at `meta.block_number` and the events
+/// in `(since, meta.block_number]`.
+pub struct SyncBatch {
+    pub meta: Meta,
+    pub since: Option<i32>,
+    pub domains: Vec<DomainRecord>,
+    pub registrations: Records,
+    pub registration_events: Vec<RegistrationEventRecord>,
+    pub transfers: Vec<TransferRecord>,
+    pub domain_events: Vec<DomainEventRecord>,
+}
+
+impl SyncBatch {
+    /// With `since`, only the domains and registrations written after it
+    /// are fetched, via `_change_block`, and the events via `blockNumber_gt`.
+    pub async fn query(meta: Meta, since: Option<i32>) -> anyhow::Result<Self> {
+        let block = meta.block_number;
+
+        let (domains, registrations, registration_events, transfers, domain_events) = match since {
+            Some(since) => (
+                DomainsBuilder::query_changed(since, block).await?,
+                RecordsBuilder::query_changed(since, block).await,
+                RegistrationEventsBuilder::query_after(since, block).await,
+                TransfersBuilder::query(
+                    &TransferFilter {
+                        after_block: Some(since),
+                        ..Default::default()
+                    },
+                    block,
+                )
+                .await,
+                DomainEventsBuilder::query_after(since, block).await,
+            ),
+            None => (
+                DomainsBuilder::query(block).await?,
+                query_all_at::<RecordsBuilder>(block).await,
+                query_all_at::<RegistrationEventsBuilder>(block).await,
+                TransfersBuilder::query(&Default::default(), block).await,
+                query_all_at::<DomainEventsBuilder>(block).await,
+            ),
+        };
+
+        Ok(Self {
+            domains,
+            registrations: Records(registrations.into_iter().collect()),
+            registration_events,
+            transfers,
+            domain_events,
+            meta,
+            since,
+        })
+    }
+
+    /// Owners and resolved addresses of domains plus everyone seen in the
+    /// batch's events, subdomain owners included.
+    pub fn accounts(&self) -> BTreeSet<&str> {
+        let domains = self.domains.iter().flat_map(|d| {
+            [d.owner.as_deref(), d.resolved_address.as_deref()]
+                .into_iter()
+                .flatten()
+        });
+        let transfers = self.transfers.iter().flat_map(|t| {
+            [t.from.as_deref(), Some(t.to.as_str())]
+                .into_iter()
+                .flatten()
+        });
+        let registrants = self
+            .registration_events
+            .iter()
+            .map(|e| e.registrant.as_str());
+        let domain_events = self.domain_events.iter().flat_map(|e| e.event.accounts());
+
+        domains
+            .chain(transfers)
+            .chain(registrants)
+            .chain(domain_events)
+            .collect()
+    }
+}
+
+pub struct Mirror {
+    client: Client,
+}
+
+impl Mirror {
+    /// Connect and create any missing tables.
+    pub async fn connect(url: &str) -> anyhow::Result<Self> {
+        let (client, connection) = tokio_postgres::connect(url, NoTls).await?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                eprintln!("postgres connection error: {e}");
+            }
+        });
+
+        client.batch_execute(SCHEMA).await?;
+        Ok(Self { client })
+    }
+
+    pub async fn last_synced_block(&self) -> anyhow::Result<Option<i32>> {
+        Ok(self.sync_state().await?.map(|(block, _)| block))
+    }
+
+    /// Block and deployment of the last sync.
+    pub async fn sync_state(&self) -> anyhow::Result<Option<(i32, String)>> {
+        let row = self
+            .client
+            .query_opt("SELECT block_number, deployment FROM sync_state", &[])
+            .await?;
+        Ok(row.map(|row| (row.get(0), row.get(1))))
+    }
+
+    /// Write `batch` in one transaction. Fails without writing anything if
+    /// another sync moved the mirror since `batch` was queried, or if it
+    /// comes from another deployment.
+    pub async fn apply(&mut self, batch: &SyncBatch) -> anyhow::Result<()> {
+        let tx = self.client.transaction().await?;
+
+        let state = tx
+            .query_opt(
+                "SELECT block_number, deployment FROM sync_state FOR UPDATE",
+                &[],
+            )
+            .await?
+            .map(|row| (row.get::<_, i32>(0), row.get::<_, String>(1)));
+        if let Some((_, deployment)) = &state {
+            check_deployment(deployment, &batch.meta)?;
+        }
+        let current = state.map(|(block, _)| block);
+        if current != batch.since {
+            anyhow::bail!(
+                "mirror is at block {current:?}, batch was queried from {:?}",
+                batch.since
+            );
+        }
+
+        upsert_accounts(&tx, batch).await?;
+        upsert_domains(&tx, batch).await?;
+        upsert_registrations(&tx, batch).await?;
+        upsert_registration_events(&tx, batch).await?;
+        upsert_transfers(&tx, batch).await?;
+        upsert_domain_events(&tx, batch).await?;
+        refresh_derived(&tx, batch).await?;
+
+        tx.execute(
+            "INSERT INTO sync_state (block_number, block_hash, deployment, synced_at)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT (id) DO UPDATE SET
+                block_number = EXCLUDED.block_number,
+                block_hash = EXCLUDED.block_hash,
+                deployment = EXCLUDED.deployment,
+                synced_at = EXCLUDED.synced_at",
+            &[
+                &batch.meta.block_number,
+                &batch.meta.block_hash,
+                &batch.meta.deployment,
+                &time::OffsetDateTime::now_utc().unix_timestamp(),
+            ],
+        )
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Sync up to the block of `meta`, usually the subgraph's current one.
+    /// Returns the new block, or `None` when the mirror was already there.
+    pub async fn sync(&mut self, meta: Meta) -> anyhow::Result<Option<i32>> {
+        let state = self.sync_state().await?;
+        // checked before the batch is queried as well, it can be large
+        if let Some((_, deployment)) = &state {
+            check_deployment(deployment, &meta)?;
+        }
+        let since = state.map(|(block, _)| block);
+        if since.is_some_and(|since| since >= meta.block_number) {
+            return Ok(None);
+        }
+
+        let batch = SyncBatch::query(meta, since).await?;
+        self.apply(&batch).await?;
+        Ok(Some(batch.meta.block_number))
+    }
+}
+
+fn check_deployment(mirrored: &str, meta: &Meta) -> anyhow::Result<()> {
+    if mirrored != meta.deployment {
+        anyhow::bail!(
+            "mirror was synced from deployment {mirrored}, the subgraph now serves {}",
+            meta.deployment
+        );
+    }
+    Ok(())
+}
+
+/// Columns copied from another entity, which an incremental batch only
+/// brings when that other entity changed: a renewal writes the registration
+/// but not its domain, a new subdomain the domain but not its registration.
+async fn refresh_derived(tx: &Transaction<'_>, batch: &SyncBatch) -> anyhow::Result<()> {
+    tx.execute(
+        "UPDATE domains d SET expire = r.expire, capacity = r.capacity
+         FROM registrations r
+         WHERE r.domain = d.id AND r.synced_block = $1
+            AND (d.expire IS DISTINCT FROM r.expire OR d.capacity IS DISTINCT FROM r.capacity)",
+        &[&batch.meta.block_number],
+    )
+    .await?;
+    tx.execute(
+        "UPDATE registrations r SET children = d.subdomain_count
+         FROM domains d
+         WHERE d.id = r.domain AND d.synced_block = $1 AND r.children <> d.subdomain_count",
+        &[&batch.meta.block_number],
+    )
+    .await?;
+    Ok(())
+}
+
+async fn upsert_accounts(tx: &Transaction<'_>, batch: &SyncBatch) -> anyhow::Result<()> {
+    let stmt = tx
+        .prepare(
+            "INSERT INTO accounts (id, synced_block) VALUES ($1, $2)
+             ON CONFLICT (id) DO UPDATE SET synced_block = EXCLUDED.synced_block",
+        )
+        .await?;
+    for account in batch.accounts() {
+        tx.execute(&stmt, &[&account, &batch.meta.block_number])
+            .await?;
+    }
+    Ok(())
+}
+
+async fn upsert_domains(tx: &Transaction<'_>, batch: &SyncBatch) -> anyhow::Result<()> {
+    let stmt = tx
+        .prepare(
+            "INSERT INTO domains VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+             ON CONFLICT (id) DO UPDATE SET
+                name = EXCLUDED.name,
+                label_name = EXCLUDED.label_name,
+                labelhash = EXCLUDED.labelhash,
+                parent = EXCLUDED.parent,
+                owner = EXCLUDED.owner,
+                resolved_address = EXCLUDED.resolved_address,
+                resolver = EXCLUDED.resolver,
+                subdomain_count = EXCLUDED.subdomain_count,
+                created_at = EXCLUDED.created_at,
+                expire = EXCLUDED.expire,
+                capacity = EXCLUDED.capacity,
+                synced_block = EXCLUDED.synced_block",
+        )
+        .await?;
+    for d in batch.domains.iter() {
+        tx.execute(
+            &stmt,
+            &[
+                &d.id,
+                &d.name,
+                &d.label_name,
+                &d.labelhash,
+                &d.parent,
+                &d.owner,
+                &d.resolved_address,
+                &d.resolver,
+                &d.subdomain_count,
+                &d.created_at,
+                &d.expire,
+                &d.capacity,
+                &batch.meta.block_number,
+            ],
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+async fn upsert_registrations(tx: &Transaction<'_>, batch: &SyncBatch) -> anyhow::Result<()> {
+    let stmt = tx
+        .prepare(
+            "INSERT INTO registrations VALUES ($1, $2, $3, $4, $5, $6)
+             ON CONFLICT (domain) DO UPDATE SET
+                origin = EXCLUDED.origin,
+                expire = EXCLUDED.expire,
+                capacity = EXCLUDED.capacity,
+                children = EXCLUDED.children,
+                synced_block = EXCLUDED.synced_block",
+        )
+        .await?;
+    for (domain, r) in batch.registrations.0.iter() {
+        tx.execute(
+            &stmt,
+            &[
+                domain,
+                &r.origin,
+                &r.expire,
+                &r.capacity,
+                &r.children,
+                &batch.meta.block_number,
+            ],
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+async fn upsert_registration_events(tx: &Transaction<'_>, batch: &SyncBatch) -> anyhow::Result<()> {
+    // the cost arrives as a decimal string, postgres parses it into NUMERIC
+    let stmt = tx
+        .prepare(
+            "INSERT INTO registration_events
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8::TEXT::NUMERIC, $9, $10)
+             ON CONFLICT (id) DO UPDATE SET
+                kind = EXCLUDED.kind,
+                domain = EXCLUDED.domain,
+                registrant = EXCLUDED.registrant,
+                block_number = EXCLUDED.block_number,
+                triggered_date = EXCLUDED.triggered_date,
+                transaction_id = EXCLUDED.transaction_id,
+                cost = EXCLUDED.cost,
+                expiry = EXCLUDED.expiry,
+                capacity = EXCLUDED.capacity",
+        )
+        .await?;
+    for e in batch.registration_events.iter() {
+        tx.execute(
+            &stmt,
+            &[
+                &e.id,
+                &e.kind.as_str(),
+                &e.domain,
+                &e.registrant,
+                &e.block_number,
+                &e.triggered_date,
+                &e.transaction_id,
+                &e.cost,
+                &e.new_expiry,
+                &e.new_capacity,
+            ],
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+async fn upsert_transfers(tx: &Transaction<'_>, batch: &SyncBatch) -> anyhow::Result<()> {
+    let stmt = tx
+        .prepare(
+            "INSERT INTO transfers VALUES ($1, $2, $3, $4, $5, $6, $7)
+             ON CONFLICT (id) DO UPDATE SET
+                domain = EXCLUDED.domain,
+                from_account = EXCLUDED.from_account,
+                to_account = EXCLUDED.to_account,
+                block_number = EXCLUDED.block_number,
+                triggered_date = EXCLUDED.triggered_date,
+                transaction_id = EXCLUDED.transaction_id",
+        )
+        .await?;
+    for t in batch.transfers.iter() {
+        tx.execute(
+            &stmt,
+            &[
+                &t.id,
+                &t.domain,
+                &t.from,
+                &t.to,
+                &t.block_number,
+                &t.triggered_date,
+                &t.transaction_id,
+            ],
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+async fn upsert_domain_events(tx: &Transaction<'_>, batch: &SyncBatch) -> anyhow::Result<()> {
+    // the fields specific to each type are kept as the JSON the exports
+    // write, postgres parses it into JSONB
+    let stmt = tx
+        .prepare(
+            "INSERT INTO domain_events VALUES ($1, $2, $3, $4, $5, $6, $7::TEXT::JSONB)
+             ON CONFLICT (id) DO UPDATE SET
+                kind = EXCLUDED.kind,
+                domain = EXCLUDED.domain,
+                block_number = EXCLUDED.block_number,
+                triggered_date = EXCLUDED.triggered_date,
+                transaction_id = EXCLUDED.transaction_id,
+                event = EXCLUDED.event",
+        )
+        .await?;
+    for e in batch.domain_events.iter() {
+        tx.execute(
+            &stmt,
+            &[
+                &e.id,
+                &e.event.type_name(),
+                &e.domain,
+                &e.block_number,
+                &e.triggered_date,
+                &e.transaction_id,
+                &serde_json::to_string(&e.event)?,
+            ],
+        )
+        .await?;
+    }
+    Ok(())
+}
@@ -1,21 +1,23 @@
 /*!
 ```
-query QueryAccounts($skip: Int = 10) {
-  accounts(first: 1000, skip: $skip) {
+query QueryAccounts($skip: Int = 10, $block: Block_height) {
+  accounts(first: 1000, skip: $skip, block: $block) {
     id
     domains(
       first: 1000
       where: {parent: "0x3fce7d1364a893e213bc4212792b517ffc88f5b13b86c8ef9c8d390c3a1370ce"}
     ) {
+      id
       name
       createdAt
     }
   }
 }
 
-query QueryDomains($skip: Int = 10, $id: ID = "") {
-  account(id: $id) {
+query QueryDomains($skip: Int = 10, $id: ID = "", $block: Block_height) {
+  account(id: $id, block: $block) {
     domains(first: 1000, skip: $skip) {
+      id
       name
       createdAt
     }
@@ -30,30 +32,35 @@ const NEW_TIMESTAMP: i32 = 1669032000;
 
 #[cynic::schema_for_derives(file = r#"schema.gql"#, module = "schema")]
 mod queries {
-    use super::schema;
+    use crate::{
+        fragments::{BlockHeight, Bytes, DomainFields},
+        schema,
+    };
 
     #[derive(cynic::QueryVariables, Debug)]
     pub struct QueryAccountsVariables {
         pub skip: Option<i32>,
+        pub block: Option<BlockHeight>,
     }
 
     #[derive(cynic::QueryVariables, Debug)]
     pub struct QueryDomainsVariables {
         pub id: cynic::Id,
         pub skip: Option<i32>,
+        pub block: Option<BlockHeight>,
     }
 
     #[derive(cynic::QueryFragment, Debug)]
     #[cynic(graphql_type = "Query", variables = "QueryAccountsVariables")]
     pub struct QueryAccounts {
-        #[arguments(first: 1000, skip: $skip)]
+        #[arguments(first: 1000, skip: $skip, block: $block)]
         pub accounts: Vec<Account>,
     }
 
     #[derive(cynic::QueryFragment, Debug)]
     #[cynic(graphql_type = "Query", variables = "QueryDomainsVariables")]
     pub struct QueryDomains {
-        #[arguments(id: $id)]
+        #[arguments(id: $id, block: $block)]
         pub account: Option<Account2>,
     }
 
@@ -61,47 +68,30 @@ mod queries {
     pub struct Account {
         pub id: Bytes,
         #[arguments(first: 1000, where: { parent: "0x3fce7d1364a893e213bc4212792b517ffc88f5b13b86c8ef9c8d390c3a1370ce" })]
-        pub domains: Vec<Domain>,
+        pub domains: Vec<DomainFields>,
     }
 
     #[derive(cynic::QueryFragment, Debug)]
     #[cynic(graphql_type = "Account", variables = "QueryDomainsVariables")]
     pub struct Account2 {
         #[arguments(first: 1000, skip: $skip)]
-        pub domains: Vec<Domain>,
+        pub domains: Vec<DomainFields>,
     }
-
-    #[derive(cynic::QueryFragment, Debug)]
-    pub struct Domain {
-        pub name: Option<String>,
-        pub created_at: BigInt,
-    }
-
-    #[derive(cynic::Scalar, Debug, Clone)]
-    pub struct BigInt(pub String);
-
-    #[derive(cynic::Scalar, Debug, Clone)]
-    pub struct Bytes(pub String);
-}
-
-#[allow(non_snake_case, non_camel_case_types)]
-mod schema {
-    cynic::use_schema!(r#"schema.gql"#);
 }
 
 use futures_util::{stream, Stream, StreamExt};
 use serde::Serialize;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 
-use crate::{run_graphql, HandleId, IsFull, IsFullAsync, ACCOUNT_ID_LEN};
+use crate::{fragments::BlockHeight, run_graphql, HandleId, IsFull, IsFullAsync, ACCOUNT_ID_LEN};
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AllAccounts {
     old_accounts_num: usize,
-    old_accounts: HashSet<Account>,
+    old_accounts: BTreeSet<Account>,
     new_accounts_num: usize,
-    new_accounts: HashSet<Account>,
+    new_accounts: BTreeSet<Account>,
 }
 
 #[derive(Debug, Serialize, Eq)]
@@ -110,7 +100,18 @@ pub struct Account {
     id: String,
     old_domains_num: usize,
     new_domains_num: usize,
-    domains: HashMap<String, i32>,
+    domains: BTreeMap<String, i32>,
+}
+
+impl AllAccounts {
+    /// Old and new accounts together.
+    pub fn len(&self) -> usize {
+        self.old_accounts_num + self.new_accounts_num
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
 impl PartialEq for Account {
@@ -125,28 +126,74 @@ impl core::hash::Hash for Account {
     }
 }
 
+impl PartialOrd for Account {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// By id, so exports list accounts in a stable order.
+impl Ord for Account {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.id.cmp(&other.id)
+    }
+}
+
 pub struct QueryNewDomainsBuilder;
 
 impl QueryNewDomainsBuilder {
     fn build_query(
         offset: i32,
+        block: i32,
         id: cynic::Id,
     ) -> cynic::Operation<queries::QueryDomains, queries::QueryDomainsVariables> {
         <queries::QueryDomains as cynic::QueryBuilder>::build(queries::QueryDomainsVariables {
             skip: Some(offset),
+            block: Some(BlockHeight::number(block)),
             id,
         })
     }
 
-    pub async fn query() -> AllAccounts {
+    /// Every account at `block`.
+    pub async fn query(block: i32) -> AllAccounts {
+        let mut old = BTreeSet::default();
+        let mut new = BTreeSet::default();
+
+        Self::query_each(block, |addr| {
+            if addr.old_domains_num == 0 {
+                new.insert(addr);
+            } else {
+                old.insert(addr);
+            }
+            Ok(())
+        })
+        .await
+        .expect("collecting accounts does not fail");
+
+        AllAccounts {
+            old_accounts_num: old.len(),
+            old_accounts: old,
+            new_accounts_num: new.len(),
+            new_accounts: new,
+        }
+    }
+
+    /// Hand every account at `block` to `on_account` as its page arrives,
+    /// each one once.
+    pub async fn query_each(
+        block: i32,
+        mut on_account: impl FnMut(Account) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
         let mut is_full = true;
         let mut offset = 0;
-        let mut old = HashSet::default();
-        let mut new = HashSet::default();
+        let mut seen = HashSet::new();
 
         while is_full {
             let data = run_graphql(<queries::QueryAccounts as cynic::QueryBuilder>::build(
-                queries::QueryAccountsVariables { skip: Some(offset) },
+                queries::QueryAccountsVariables {
+                    skip: Some(offset),
+                    block: Some(BlockHeight::number(block)),
+                },
             ))
             .await
             .data
@@ -154,28 +201,21 @@ impl QueryNewDomainsBuilder {
             offset += 1000;
             is_full = data.is_full();
 
-            let mut stream = data.into_stream().await.boxed();
+            let mut stream = data.into_stream(block).await.boxed();
 
             while let Some(addr) = stream.next().await {
-                if addr.old_domains_num == 0 {
-                    new.insert(addr);
-                } else {
-                    old.insert(addr);
+                if seen.insert(addr.id.clone()) {
+                    on_account(addr)?;
                 }
             }
         }
 
-        AllAccounts {
-            old_accounts_num: old.len(),
-            old_accounts: old,
-            new_accounts_num: new.len(),
-            new_accounts: new,
-        }
+        Ok(())
     }
 }
 
 impl IsFull for queries::QueryDomains {
-    type Item = HashMap<String, i32>;
+    type Item = BTreeMap<String, i32>;
 
     fn len(&self) -> usize {
         self.account
@@ -198,7 +238,7 @@ impl IsFull for queries::QueryDomains {
 
                         Some((d.name.unwrap(), created_at))
                     })
-                    .collect::<HashMap<_, _>>()
+                    .collect::<BTreeMap<_, _>>()
             })
             .into_iter()
     }
@@ -211,8 +251,8 @@ impl IsFullAsync for queries::QueryAccounts {
         self.accounts.len()
     }
 
-    async fn into_stream(self) -> impl Stream<Item = Self::Item> {
-        stream::iter(self.accounts.into_iter()).filter_map(|account| async move {
+    async fn into_stream(self, block: i32) -> impl Stream<Item = Self::Item> {
+        stream::iter(self.accounts.into_iter()).filter_map(move |account| async move {
             let domains_len = account.domains.len();
             let mut full = None;
             if domains_len == 1000 {
@@ -223,6 +263,7 @@ impl IsFullAsync for queries::QueryAccounts {
                 while is_full {
                     let data = run_graphql(QueryNewDomainsBuilder::build_query(
                         offset,
+                        block,
                         cynic::Id::new(&account.id.0),
                     ))
                     .await
@@ -249,7 +290,7 @@ impl IsFullAsync for queries::QueryAccounts {
 
                     Some((d.name.unwrap(), created_at))
                 })
-                .collect::<HashMap<_, _>>();
+                .collect::<BTreeMap<_, _>>();
             if let Some(full) = full {
                 full.into_iter().for_each(|set| domains.extend(set));
             }
@@ -1,7 +1,7 @@
 /*!
 ```
-query QueryAccounts($skip: Int = 10,) {
-  domainEvents(first: 1000, skip: $skip) {
+query QueryAccounts($skip: Int = 10, $block: Block_height, $filter: DomainEvent_filter) {
+  domainEvents(first: 1000, skip: $skip, block: $block, where: $filter, orderBy: blockNumber, orderDirection: asc) {
     ... on NewSubdomain {
       name
       to {
@@ -20,36 +20,38 @@ query QueryAccounts($skip: Int = 10,) {
 */
 #[cynic::schema_for_derives(file = r#"schema.gql"#, module = "schema")]
 mod queries {
-    use super::schema;
+    use crate::{
+        fragments::{AccountFields, BlockHeight, DomainId},
+        schema,
+    };
 
     #[derive(cynic::QueryVariables, Debug)]
     pub struct NewSubdomainsVariables {
         pub skip: Option<i32>,
+        pub block: Option<BlockHeight>,
+        pub filter: Option<DomainEventFilter>,
+    }
+
+    #[derive(cynic::InputObject, Debug, Default, Clone)]
+    #[cynic(graphql_type = "DomainEvent_filter")]
+    pub struct DomainEventFilter {
+        #[cynic(rename = "blockNumber_gt", skip_serializing_if = "Option::is_none")]
+        pub block_number_gt: Option<i32>,
     }
 
     #[derive(cynic::QueryFragment, Debug)]
     #[cynic(graphql_type = "Query", variables = "NewSubdomainsVariables")]
     pub struct NewSubdomains {
-        #[arguments(first: 1000, skip: $skip)]
+        #[arguments(first: 1000, skip: $skip, block: $block, where: $filter, orderBy: blockNumber, orderDirection: asc)]
         pub domain_events: Vec<DomainEvent>,
     }
 
     #[derive(cynic::QueryFragment, Debug)]
     pub struct NewSubdomain {
         pub name: String,
-        pub to: Account,
-        pub parent_id: Domain,
-        pub domain: Domain,
-    }
-
-    #[derive(cynic::QueryFragment, Debug)]
-    pub struct Domain {
-        pub id: Bytes,
-    }
-
-    #[derive(cynic::QueryFragment, Debug)]
-    pub struct Account {
-        pub id: Bytes,
+        pub to: AccountFields,
+        pub parent_id: DomainId,
+        pub domain: DomainId,
     }
 
     #[derive(cynic::InlineFragments, Debug)]
@@ -58,40 +60,58 @@ mod queries {
         #[cynic(fallback)]
         Unknown,
     }
-
-    #[derive(cynic::Scalar, Debug, Clone)]
-    pub struct Bytes(pub String);
-}
-
-#[allow(non_snake_case, non_camel_case_types)]
-mod schema {
-    cynic::use_schema!(r#"schema.gql"#);
 }
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-use crate::{BuildQuery, HandleId, IsFull, ACCOUNT_ID_LEN, DOMAIN_ID_LEN};
+use crate::{
+    fragments::BlockHeight, output::Tabular, query_pages, BuildQuery, HandleId, IsFull,
+    ACCOUNT_ID_LEN, DOMAIN_ID_LEN,
+};
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct NewSubdomain {
-    to: String,
-    token_id: String,
-    subtoken_id: String,
-    name: String,
+    pub to: String,
+    pub token_id: String,
+    pub subtoken_id: String,
+    pub name: String,
 }
 
 pub struct NewSubdomainQueryBuilder;
 
+impl NewSubdomainQueryBuilder {
+    fn build_query_filtered(
+        offset: i32,
+        block: i32,
+        filter: queries::DomainEventFilter,
+    ) -> cynic::Operation<queries::NewSubdomains, queries::NewSubdomainsVariables> {
+        <queries::NewSubdomains as cynic::QueryBuilder>::build(queries::NewSubdomainsVariables {
+            skip: Some(offset),
+            block: Some(BlockHeight::number(block)),
+            filter: Some(filter),
+        })
+    }
+
+    /// Subdomains created in any block after `after`, up to `block`. Events
+    /// come in block order, so these follow the ones of a full query at
+    /// `after`.
+    pub async fn query_after(after: i32, block: i32) -> Vec<NewSubdomain> {
+        let filter = queries::DomainEventFilter {
+            block_number_gt: Some(after),
+        };
+
+        query_pages(|offset| Self::build_query_filtered(offset, block, filter.clone())).await
+    }
+}
+
 impl BuildQuery for NewSubdomainQueryBuilder {
     type Vars = queries::NewSubdomainsVariables;
 
     type ResponseData = queries::NewSubdomains;
 
-    fn build_query(offset: i32) -> cynic::Operation<Self::ResponseData, Self::Vars> {
-        <queries::NewSubdomains as cynic::QueryBuilder>::build(queries::NewSubdomainsVariables {
-            skip: Some(offset),
-        })
+    fn build_query(offset: i32, block: i32) -> cynic::Operation<Self::ResponseData, Self::Vars> {
+        Self::build_query_filtered(offset, block, Default::default())
     }
 }
 
@@ -119,3 +139,11 @@ impl IsFull for queries::NewSubdomains {
         self.domain_events.len()
     }
 }
+
+impl Tabular for Vec<NewSubdomain> {
+    type Row<'a> = &'a NewSubdomain;
+
+    fn rows(&self) -> impl Iterator<Item = Self::Row<'_>> {
+        self.iter()
+    }
+}
@@ -0,0 +1,543 @@
+//! Output formats shared by the exporters.
+//!
+//! JSON writes the dataset as one pretty document, as before. CSV needs one
+//! flat table, so datasets opt in by implementing [`Tabular`]. NDJSON writes
+//! one record per line and is meant to be streamed with [`NdjsonWriter`] as
+//! pages arrive. SQLite holds several related tables and is only written for
+//! a whole [`crate::snapshot::Snapshot`]. Parquet is written for datasets
+//! implementing [`Columnar`].
+//!
+//! Every export carries an [`envelope::Header`] with its provenance. CSV and
+//! NDJSON files hold records only, their header goes in a `.header.json`
+//! file next to them.
+//!
+//! Any of them can be compressed with gzip or zstd on write, [`read_file`]
+//! undoes it on read. Every file gets a `.sha256` file next to it in
+//! `sha256sum` format, and the checksum of its records in its header, see
+//! [`Header::checksum`]. Datasets serialize in a stable order, so the same
+//! chain state gives the same bytes and the same checksums.
+use std::{
+    fmt,
+    io::{Read, Write},
+    str::FromStr,
+};
+
+use flate2::{read::MultiGzDecoder, write::GzEncoder};
+use sha2::{Digest, Sha256};
+
+use serde::Serialize;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    sync::mpsc,
+    task::JoinHandle,
+};
+
+use crate::{
+    columnar::{self, Columnar},
+    envelope::{Envelope, Header},
+    resolvers::hex_encode,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    #[default]
+    Json,
+    Csv,
+    Ndjson,
+    Sqlite,
+    Parquet,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Csv => "csv",
+            Format::Ndjson => "ndjson",
+            Format::Sqlite => "sqlite",
+            Format::Parquet => "parquet",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            "ndjson" => Ok(Format::Ndjson),
+            "sqlite" => Ok(Format::Sqlite),
+            "parquet" => Ok(Format::Parquet),
+            _ => {
+                anyhow::bail!("unknown format `{s}`, expected json, csv, ndjson, sqlite or parquet")
+            }
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    const GZIP_MAGIC: &'static [u8] = &[0x1f, 0x8b];
+    const ZSTD_MAGIC: &'static [u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+    /// Appended after the format's extension, e.g. `json.gz`.
+    pub fn extension(self) -> Option<&'static str> {
+        match self {
+            Compression::None => None,
+            Compression::Gzip => Some("gz"),
+            Compression::Zstd => Some("zst"),
+        }
+    }
+
+    /// By the last extension of `path`.
+    pub fn from_path(path: &str) -> Self {
+        match path.rsplit_once('.').map(|(_, ext)| ext) {
+            Some("gz") => Compression::Gzip,
+            Some("zst") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+
+    /// By the magic number at the start of `bytes`.
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.starts_with(Self::GZIP_MAGIC) {
+            Compression::Gzip
+        } else if bytes.starts_with(Self::ZSTD_MAGIC) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+
+    pub fn encoder<W: Write>(self, inner: W) -> anyhow::Result<Encoder<W>> {
+        Ok(match self {
+            Compression::None => Encoder::None(inner),
+            Compression::Gzip => Encoder::Gzip(GzEncoder::new(inner, Default::default())),
+            Compression::Zstd => Encoder::Zstd(zstd::Encoder::new(inner, 0)?),
+        })
+    }
+
+    pub fn compress(self, bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+        if self == Compression::None {
+            return Ok(bytes.to_vec());
+        }
+        let mut encoder = self.encoder(Vec::new())?;
+        encoder.write_all(bytes)?;
+        encoder.finish()
+    }
+
+    pub fn decompress(self, bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let mut res = Vec::new();
+        match self {
+            Compression::None => res.extend_from_slice(bytes),
+            Compression::Gzip => {
+                MultiGzDecoder::new(bytes).read_to_end(&mut res)?;
+            }
+            Compression::Zstd => {
+                zstd::Decoder::new(bytes)?.read_to_end(&mut res)?;
+            }
+        }
+        Ok(res)
+    }
+}
+
+impl FromStr for Compression {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Compression::None),
+            "gzip" | "gz" => Ok(Compression::Gzip),
+            "zstd" | "zst" => Ok(Compression::Zstd),
+            _ => anyhow::bail!("unknown compression `{s}`, expected none, gzip or zstd"),
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Compression::None => "none",
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+        })
+    }
+}
+
+/// A writer compressing with [`Compression`]. [`Encoder::finish`] must be
+/// called to write the trailer.
+pub enum Encoder<W: Write> {
+    None(W),
+    Gzip(GzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> Encoder<W> {
+    /// The writer compressed bytes go to.
+    pub fn get_mut(&mut self) -> &mut W {
+        match self {
+            Encoder::None(inner) => inner,
+            Encoder::Gzip(encoder) => encoder.get_mut(),
+            Encoder::Zstd(encoder) => encoder.get_mut(),
+        }
+    }
+
+    pub fn finish(self) -> anyhow::Result<W> {
+        Ok(match self {
+            Encoder::None(mut inner) => {
+                inner.flush()?;
+                inner
+            }
+            Encoder::Gzip(encoder) => encoder.finish()?,
+            Encoder::Zstd(encoder) => encoder.finish()?,
+        })
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Encoder::None(inner) => inner.write(buf),
+            Encoder::Gzip(encoder) => encoder.write(buf),
+            Encoder::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Encoder::None(inner) => inner.flush(),
+            Encoder::Gzip(encoder) => encoder.flush(),
+            Encoder::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// A dataset that flattens into one row per record. Nested lists become one
+/// row per parent/child pair.
+pub trait Tabular {
+    type Row<'a>: Serialize
+    where
+        Self: 'a;
+
+    fn rows(&self) -> impl Iterator<Item = Self::Row<'_>>;
+}
+
+pub fn to_csv<T: Tabular>(value: &T) -> anyhow::Result<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for row in value.rows() {
+        writer.serialize(row)?;
+    }
+    Ok(writer.into_inner()?)
+}
+
+/// The rows of `value`, one JSON object per line.
+pub fn to_ndjson<T: Tabular>(value: &T) -> anyhow::Result<Vec<u8>> {
+    let mut res = Vec::new();
+    for row in value.rows() {
+        serde_json::to_writer(&mut res, &row)?;
+        res.push(b'\n');
+    }
+    Ok(res)
+}
+
+/// `value` as `format`. CSV and NDJSON have no room for `header`, see
+/// [`write_file`].
+pub fn encode<T: Serialize + Tabular>(
+    header: &Header,
+    value: &T,
+    format: Format,
+) -> anyhow::Result<Vec<u8>> {
+    match format {
+        Format::Json => to_json(header, value),
+        Format::Csv => to_csv(value),
+        Format::Ndjson => to_ndjson(value),
+        Format::Sqlite => anyhow::bail!("sqlite is only written for a snapshot"),
+        Format::Parquet => anyhow::bail!("parquet is written with `write_parquet`"),
+    }
+}
+
+/// `{name}{unix timestamp}.{extension}[.gz|.zst]`, the name every exporter
+/// writes to.
+pub fn file_name(name: &str, format: Format, compression: Compression) -> String {
+    let mut res = format!(
+        "{name}{}.{}",
+        time::OffsetDateTime::now_utc().unix_timestamp(),
+        format.extension()
+    );
+    if let Some(ext) = compression.extension() {
+        res.push('.');
+        res.push_str(ext);
+    }
+    res
+}
+
+/// SHA-256 of `bytes`, lowercase hex.
+pub fn checksum(bytes: &[u8]) -> String {
+    hex_encode(&Sha256::digest(bytes))
+}
+
+/// [`Header::checksum`] of a JSON dataset, the SHA-256 of `value` as compact
+/// JSON.
+pub fn json_checksum<T: Serialize + ?Sized>(value: &T) -> anyhow::Result<String> {
+    Ok(checksum(&serde_json::to_vec(value)?))
+}
+
+/// `{checksum}  {file name}`, as `sha256sum -c` expects it.
+fn checksum_line(path: &str, checksum: &str) -> String {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    format!("{checksum}  {file_name}\n")
+}
+
+/// Hash the finished file at `path` and write `{path}.sha256`, for files
+/// not written through [`write_bytes`]. Returns the checksum.
+pub async fn write_checksum(path: &str) -> anyhow::Result<String> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; CHUNK_SIZE];
+    loop {
+        let len = file.read(&mut buf).await?;
+        if len == 0 {
+            break;
+        }
+        hasher.update(&buf[..len]);
+    }
+
+    let checksum = hex_encode(&hasher.finalize());
+    tokio::fs::write(format!("{path}.sha256"), checksum_line(path, &checksum)).await?;
+    Ok(checksum)
+}
+
+/// Write `header` to `{path}.header.json`, for formats without room for it.
+async fn write_header(path: &str, header: &Header) -> anyhow::Result<()> {
+    tokio::fs::write(
+        format!("{path}.header.json"),
+        serde_json::to_vec_pretty(header)?,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Compress `bytes` and write them to [`file_name`] in the working
+/// directory, with their checksum next to them. Returns the path.
+pub async fn write_bytes(
+    name: &str,
+    format: Format,
+    compression: Compression,
+    bytes: &[u8],
+) -> anyhow::Result<String> {
+    let path = file_name(name, format, compression);
+    let bytes = compression.compress(bytes)?;
+
+    let mut file = tokio::fs::File::create(&path).await?;
+    file.write_all(&bytes).await?;
+    tokio::fs::write(
+        format!("{path}.sha256"),
+        checksum_line(&path, &checksum(&bytes)),
+    )
+    .await?;
+
+    Ok(path)
+}
+
+/// Write `value` as `header.dataset`. CSV and NDJSON get their header, with
+/// the checksum of the uncompressed file, in a `.header.json` file next to
+/// them.
+pub async fn write_file<T: Serialize + Tabular>(
+    header: &Header,
+    value: &T,
+    format: Format,
+    compression: Compression,
+) -> anyhow::Result<()> {
+    let bytes = encode(header, value, format)?;
+    let path = write_bytes(&header.dataset, format, compression, &bytes).await?;
+
+    if matches!(format, Format::Csv | Format::Ndjson) {
+        write_header(&path, &header.clone().with_checksum(checksum(&bytes))).await?;
+    }
+
+    Ok(())
+}
+
+/// `{"header": .., "data": ..}`, pretty printed, with the checksum of `value`
+/// in the header.
+pub fn to_json<T: Serialize>(header: &Header, value: &T) -> anyhow::Result<Vec<u8>> {
+    Ok(serde_json::to_vec_pretty(&Envelope {
+        header: header.clone().with_checksum(json_checksum(value)?),
+        data: value,
+    })?)
+}
+
+/// Pretty JSON, for the exporters without a [`Tabular`] dataset.
+pub async fn write_json<T: Serialize>(
+    header: &Header,
+    value: &T,
+    compression: Compression,
+) -> anyhow::Result<()> {
+    let bytes = to_json(header, value)?;
+    write_bytes(&header.dataset, Format::Json, compression, &bytes).await?;
+
+    Ok(())
+}
+
+pub async fn write_parquet<T: Columnar>(
+    header: &Header,
+    value: &T,
+    compression: Compression,
+) -> anyhow::Result<()> {
+    let bytes = columnar::to_parquet(header, value)?;
+    write_bytes(&header.dataset, Format::Parquet, compression, &bytes).await?;
+
+    Ok(())
+}
+
+/// Compress the finished file at `path` in place, e.g. a SQLite database
+/// that has to be written uncompressed first. Returns the new path.
+pub async fn compress_file(path: &str, compression: Compression) -> anyhow::Result<String> {
+    let Some(ext) = compression.extension() else {
+        return Ok(path.to_string());
+    };
+
+    let compressed = format!("{path}.{ext}");
+    let bytes = tokio::fs::read(path).await?;
+    tokio::fs::write(&compressed, compression.compress(&bytes)?).await?;
+    tokio::fs::remove_file(path).await?;
+
+    Ok(compressed)
+}
+
+/// Read a file written by any exporter, decompressing gzip and zstd by
+/// their magic number. A `.gz` or `.zst` file that doesn't start with the
+/// matching magic number is an error, it was truncated or mislabelled.
+pub async fn read_file(path: &str) -> anyhow::Result<Vec<u8>> {
+    let bytes = tokio::fs::read(path).await?;
+    let compression = Compression::detect(&bytes);
+    let expected = Compression::from_path(path);
+    if expected != Compression::None && expected != compression {
+        anyhow::bail!("`{path}` is named as {expected} but holds {compression} data");
+    }
+    compression.decompress(&bytes)
+}
+
+/// Compressed bytes [`NdjsonWriter`] buffers before handing them to its file.
+const CHUNK_SIZE: usize = 1 << 16;
+
+/// Appends one JSON document per line, so an export can be consumed while it
+/// is still running and never has to fit in memory.
+///
+/// Lines are compressed as they are written and handed in chunks to a task
+/// writing the file, so [`NdjsonWriter::write`] never blocks and can be
+/// called from a page callback.
+pub struct NdjsonWriter {
+    inner: Encoder<Vec<u8>>,
+    chunks: mpsc::UnboundedSender<Vec<u8>>,
+    /// Writes the chunks, returns the checksum of the file.
+    task: JoinHandle<anyhow::Result<String>>,
+    header: Header,
+    path: String,
+    len: usize,
+    /// Of the record lines, for the header.
+    hasher: Sha256,
+}
+
+impl NdjsonWriter {
+    /// Create [`file_name`] of `header.dataset` with the `ndjson` extension.
+    pub async fn create(header: &Header, compression: Compression) -> anyhow::Result<Self> {
+        let path = file_name(&header.dataset, Format::Ndjson, compression);
+        Self::create_at(path, header, compression).await
+    }
+
+    /// Create the file at `path`.
+    pub async fn create_at(
+        path: String,
+        header: &Header,
+        compression: Compression,
+    ) -> anyhow::Result<Self> {
+        let mut file = tokio::fs::File::create(&path).await?;
+        let (chunks, mut received) = mpsc::unbounded_channel::<Vec<u8>>();
+        let task = tokio::spawn(async move {
+            let mut hasher = Sha256::new();
+            while let Some(chunk) = received.recv().await {
+                hasher.update(&chunk);
+                file.write_all(&chunk).await?;
+            }
+            file.flush().await?;
+            Ok(hex_encode(&hasher.finalize()))
+        });
+
+        Ok(Self {
+            inner: compression.encoder(Vec::new())?,
+            chunks,
+            task,
+            header: header.clone(),
+            path,
+            len: 0,
+            hasher: Sha256::new(),
+        })
+    }
+
+    pub fn write<T: Serialize>(&mut self, item: &T) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(item)?;
+        line.push(b'\n');
+        self.hasher.update(&line);
+        self.inner.write_all(&line)?;
+        self.len += 1;
+
+        if self.inner.get_mut().len() >= CHUNK_SIZE {
+            let chunk = std::mem::take(self.inner.get_mut());
+            // the task only stops early on an error, `finish` returns it
+            self.chunks
+                .send(chunk)
+                .map_err(|_| anyhow::anyhow!("writing {} failed", self.path))?;
+        }
+        Ok(())
+    }
+
+    /// Records written so far.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Flush the file, write the `.sha256` file and the header, with the
+    /// record count and checksum, to `.header.json`. Returns the number of
+    /// records written.
+    pub async fn finish(self) -> anyhow::Result<usize> {
+        let rest = self.inner.finish()?;
+        // a closed channel means the task failed, its error is returned below
+        let _ = self.chunks.send(rest);
+        drop(self.chunks);
+        let file_checksum = self.task.await??;
+
+        tokio::fs::write(
+            format!("{}.sha256", self.path),
+            checksum_line(&self.path, &file_checksum),
+        )
+        .await?;
+
+        let header = self
+            .header
+            .with_record_count(self.len)
+            .with_checksum(hex_encode(&self.hasher.finalize()));
+        write_header(&self.path, &header).await?;
+
+        Ok(self.len)
+    }
+}
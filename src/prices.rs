@@ -0,0 +1,239 @@
+/*!
+```
+query QueryPriceChangeds($skip: Int = 10, $block: Block_height) {
+  priceChangeds(skip: $skip, first: 1000, block: $block, orderBy: blockNumber, orderDirection: asc) {
+    id
+    blockNumber
+    triggeredDate
+    transactionID
+    basePrices
+    rentPrices
+  }
+}
+```
+
+`basePrices[i]` and `rentPrices[i]` price a label of `i + 1` characters, the
+last entry covers every longer label. Rent is charged per second of
+registration.
+*/
+#[cynic::schema_for_derives(file = r#"schema.gql"#, module = "schema")]
+mod queries {
+    use crate::{
+        fragments::{BigInt, BlockHeight, Bytes},
+        schema,
+    };
+
+    #[derive(cynic::QueryVariables, Debug)]
+    pub struct QueryPriceChangedsVariables {
+        pub skip: Option<i32>,
+        pub block: Option<BlockHeight>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    #[cynic(graphql_type = "Query", variables = "QueryPriceChangedsVariables")]
+    pub struct QueryPriceChangeds {
+        #[arguments(skip: $skip, first: 1000, block: $block, orderBy: blockNumber, orderDirection: asc)]
+        pub price_changeds: Vec<PriceChanged>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    pub struct PriceChanged {
+        pub id: cynic::Id,
+        pub block_number: i32,
+        pub triggered_date: BigInt,
+        #[cynic(rename = "transactionID")]
+        pub transaction_id: Bytes,
+        pub base_prices: Vec<BigInt>,
+        pub rent_prices: Vec<BigInt>,
+    }
+}
+
+use primitive_types::U256;
+use serde::Serialize;
+
+use crate::{
+    fragments::{BigInt, BlockHeight},
+    registration_events::{RegistrationEventKind, RegistrationHistory},
+    revenue::{name_length, parse_wei},
+    BuildQuery, IsFull,
+};
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PriceChange {
+    pub id: String,
+    pub block_number: i32,
+    pub triggered_date: i64,
+    pub transaction_id: String,
+    pub prices: Vec<LengthPrice>,
+}
+
+/// Price of a label with `length` characters, or at least `length` for the
+/// last entry of a [`PriceChange`]. A price is `None` when the event's base
+/// and rent lists differ in length and this one has no entry for `length`.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LengthPrice {
+    pub length: usize,
+    pub base_price: Option<String>,
+    pub rent_price: Option<String>,
+}
+
+pub struct PriceChangesBuilder;
+
+impl BuildQuery for PriceChangesBuilder {
+    type Vars = queries::QueryPriceChangedsVariables;
+
+    type ResponseData = queries::QueryPriceChangeds;
+
+    fn build_query(offset: i32, block: i32) -> cynic::Operation<Self::ResponseData, Self::Vars> {
+        <queries::QueryPriceChangeds as cynic::QueryBuilder>::build(
+            queries::QueryPriceChangedsVariables {
+                skip: Some(offset),
+                block: Some(BlockHeight::number(block)),
+            },
+        )
+    }
+}
+
+impl IsFull for queries::QueryPriceChangeds {
+    type Item = PriceChange;
+
+    fn len(&self) -> usize {
+        self.price_changeds.len()
+    }
+
+    fn into_iter(self) -> impl IntoIterator<Item = Self::Item> {
+        IntoIterator::into_iter(self.price_changeds).map(|change| {
+            let len = change.base_prices.len().max(change.rent_prices.len());
+            let price_at = |prices: &[BigInt], i: usize| prices.get(i).map(|p| p.0.clone());
+
+            PriceChange {
+                id: change.id.inner().to_string(),
+                block_number: change.block_number,
+                triggered_date: change.triggered_date.0.parse().unwrap(),
+                transaction_id: change.transaction_id.0,
+                prices: (0..len)
+                    .map(|i| LengthPrice {
+                        length: i + 1,
+                        base_price: price_at(&change.base_prices, i),
+                        rent_price: price_at(&change.rent_prices, i),
+                    })
+                    .collect(),
+            }
+        })
+    }
+}
+
+/// Every price change, in block order.
+#[derive(Debug, Serialize)]
+pub struct PriceSchedule(pub Vec<PriceChange>);
+
+impl PriceSchedule {
+    /// The prices in force at `timestamp`.
+    pub fn at(&self, timestamp: i64) -> Option<&PriceChange> {
+        self.0
+            .iter()
+            .take_while(|change| change.triggered_date <= timestamp)
+            .last()
+    }
+
+    /// Wei to register a label of `length` characters for `duration` seconds
+    /// at `timestamp`. `None` if the schedule has no price for it.
+    pub fn price(&self, length: usize, timestamp: i64, duration: i64) -> Option<U256> {
+        let prices = &self.at(timestamp)?.prices;
+        let price = prices.get(length.max(1) - 1).or(prices.last())?;
+
+        let rent = parse_wei(price.rent_price.as_deref()?)
+            .ok()?
+            .checked_mul(U256::from(duration.max(0)))?;
+        parse_wei(price.base_price.as_deref()?)
+            .ok()?
+            .checked_add(rent)
+    }
+}
+
+/// A paid cost next to what the schedule says it should have been.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CostCheck {
+    pub event: String,
+    pub domain: String,
+    pub name: Option<String>,
+    pub kind: RegistrationEventKind,
+    pub duration: i64,
+    pub cost_wei: String,
+    pub expected_wei: Option<String>,
+}
+
+/// Registrations and renewals whose cost differs from the schedule. The
+/// duration is taken from the expiry before and after each event.
+pub fn check_costs(histories: &[RegistrationHistory], schedule: &PriceSchedule) -> Vec<CostCheck> {
+    let mut res = Vec::new();
+
+    for event in histories.iter().flat_map(|h| h.events.iter()) {
+        let start = match event.kind {
+            RegistrationEventKind::NameRegistered => Some(event.triggered_date),
+            RegistrationEventKind::NameRenewed => event.old_expiry,
+            RegistrationEventKind::CapacityUpdated => None,
+        };
+        let (Some(start), Some(end), Some(cost)) = (start, event.new_expiry, &event.cost) else {
+            continue;
+        };
+
+        let duration = end - start;
+        let expected = schedule.price(
+            name_length(event.name.as_deref()),
+            event.triggered_date,
+            duration,
+        );
+        if expected.is_some() && expected == parse_wei(cost).ok() {
+            continue;
+        }
+
+        res.push(CostCheck {
+            event: event.id.clone(),
+            domain: event.domain.clone(),
+            name: event.name.clone(),
+            kind: event.kind,
+            duration,
+            cost_wei: cost.clone(),
+            expected_wei: expected.map(|e| e.to_string()),
+        });
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(length: usize, base: Option<&str>, rent: Option<&str>) -> LengthPrice {
+        LengthPrice {
+            length,
+            base_price: base.map(str::to_string),
+            rent_price: rent.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn lengths_without_both_prices_have_no_price() {
+        let schedule = PriceSchedule(vec![PriceChange {
+            id: "0xa-1".to_string(),
+            block_number: 1,
+            triggered_date: 100,
+            transaction_id: String::new(),
+            prices: vec![
+                price(1, Some("1000"), Some("10")),
+                price(2, Some("500"), None),
+            ],
+        }]);
+
+        assert_eq!(schedule.price(1, 100, 3), Some(U256::from(1030)));
+        assert_eq!(schedule.price(2, 100, 3), None);
+        // longer labels take the last entry
+        assert_eq!(schedule.price(5, 100, 3), None);
+        assert_eq!(schedule.price(1, 99, 3), None);
+    }
+}
@@ -0,0 +1,432 @@
+/*!
+```
+query QueryRegistrationEvents($skip: Int = 10, $block: Block_height, $filter: RegistrationEvent_filter) {
+  registrationEvents(
+    skip: $skip
+    first: 1000
+    block: $block
+    where: $filter
+    orderBy: blockNumber
+    orderDirection: asc
+  ) {
+    ... on NameRegistered {
+      ...EventFields
+      expiryDate
+      cost
+    }
+    ... on NameRenewed {
+      ...EventFields
+      expiryDate
+      cost
+    }
+    ... on CapacityUpdated {
+      ...EventFields
+      capacity
+    }
+  }
+}
+
+fragment EventFields on RegistrationEvent {
+  id
+  blockNumber
+  triggeredDate
+  transactionID
+  registrant {
+    id
+  }
+  registration {
+    domain {
+      id
+      name
+      createdAt
+    }
+  }
+}
+```
+*/
+#[cynic::schema_for_derives(file = r#"schema.gql"#, module = "schema")]
+mod queries {
+    use crate::{
+        fragments::{AccountFields, BigInt, BlockHeight, Bytes, DomainFields},
+        schema,
+    };
+
+    #[derive(cynic::QueryVariables, Debug)]
+    pub struct QueryRegistrationEventsVariables {
+        pub skip: Option<i32>,
+        pub block: Option<BlockHeight>,
+        pub filter: Option<RegistrationEventFilter>,
+    }
+
+    #[derive(cynic::InputObject, Debug, Default, Clone)]
+    #[cynic(graphql_type = "RegistrationEvent_filter")]
+    pub struct RegistrationEventFilter {
+        #[cynic(skip_serializing_if = "Option::is_none")]
+        pub registrant: Option<String>,
+        #[cynic(rename = "registration_", skip_serializing_if = "Option::is_none")]
+        pub registration_: Option<RegistrationFilter>,
+        #[cynic(rename = "blockNumber_gt", skip_serializing_if = "Option::is_none")]
+        pub block_number_gt: Option<i32>,
+    }
+
+    #[derive(cynic::InputObject, Debug, Default, Clone)]
+    #[cynic(graphql_type = "Registration_filter")]
+    pub struct RegistrationFilter {
+        #[cynic(skip_serializing_if = "Option::is_none")]
+        pub domain: Option<String>,
+        #[cynic(rename = "domain_in", skip_serializing_if = "Option::is_none")]
+        pub domain_in: Option<Vec<String>>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    #[cynic(graphql_type = "Query", variables = "QueryRegistrationEventsVariables")]
+    pub struct QueryRegistrationEvents {
+        #[arguments(skip: $skip, first: 1000, block: $block, where: $filter, orderBy: blockNumber, orderDirection: asc)]
+        pub registration_events: Vec<RegistrationEvent>,
+    }
+
+    #[derive(cynic::InlineFragments, Debug)]
+    pub enum RegistrationEvent {
+        NameRegistered(NameRegistered),
+        NameRenewed(NameRenewed),
+        CapacityUpdated(CapacityUpdated),
+        #[cynic(fallback)]
+        Unknown,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    pub struct NameRegistered {
+        pub id: cynic::Id,
+        pub block_number: i32,
+        pub triggered_date: BigInt,
+        #[cynic(rename = "transactionID")]
+        pub transaction_id: Bytes,
+        pub registrant: AccountFields,
+        pub registration: Registration,
+        pub expiry_date: BigInt,
+        pub cost: Option<BigInt>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    pub struct NameRenewed {
+        pub id: cynic::Id,
+        pub block_number: i32,
+        pub triggered_date: BigInt,
+        #[cynic(rename = "transactionID")]
+        pub transaction_id: Bytes,
+        pub registrant: AccountFields,
+        pub registration: Registration,
+        pub expiry_date: BigInt,
+        pub cost: Option<BigInt>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    pub struct CapacityUpdated {
+        pub id: cynic::Id,
+        pub block_number: i32,
+        pub triggered_date: BigInt,
+        #[cynic(rename = "transactionID")]
+        pub transaction_id: Bytes,
+        pub registrant: AccountFields,
+        pub registration: Registration,
+        pub capacity: BigInt,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    pub struct Registration {
+        pub domain: DomainFields,
+    }
+}
+
+use std::collections::{BTreeMap, HashMap};
+
+use arrow_array::{builder::StringBuilder, RecordBatch};
+use arrow_schema::Schema;
+use serde::Serialize;
+
+use crate::{
+    columnar::{self, AccountId, Cell, Columnar, DomainId, Hash, Text, Wei, U32, U64},
+    fragments::BlockHeight,
+    output::Tabular,
+    query_pages, BuildQuery, HandleId, IsFull, ACCOUNT_ID_LEN, DOMAIN_ID_LEN,
+};
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RegistrationEventKind {
+    NameRegistered,
+    NameRenewed,
+    CapacityUpdated,
+}
+
+impl RegistrationEventKind {
+    /// The variant name, as serialized and as stored by the exports and the
+    /// mirror.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RegistrationEventKind::NameRegistered => "NameRegistered",
+            RegistrationEventKind::NameRenewed => "NameRenewed",
+            RegistrationEventKind::CapacityUpdated => "CapacityUpdated",
+        }
+    }
+}
+
+/// One step of a name's registration history. The `old_*` fields are filled
+/// in by [`histories`] from the events before it.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RegistrationEventRecord {
+    pub id: String,
+    pub kind: RegistrationEventKind,
+    pub domain: String,
+    pub name: Option<String>,
+    pub registrant: String,
+    pub block_number: i32,
+    pub triggered_date: i64,
+    pub transaction_id: String,
+    /// Wei, as the exact decimal string the subgraph returns.
+    pub cost: Option<String>,
+    pub old_expiry: Option<i64>,
+    pub new_expiry: Option<i64>,
+    pub old_capacity: Option<i64>,
+    pub new_capacity: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegistrationHistory {
+    pub domain: String,
+    pub name: Option<String>,
+    pub events: Vec<RegistrationEventRecord>,
+}
+
+/// Events as they came, without grouping into histories.
+impl Tabular for Vec<RegistrationEventRecord> {
+    type Row<'a> = &'a RegistrationEventRecord;
+
+    fn rows(&self) -> impl Iterator<Item = Self::Row<'_>> {
+        self.iter()
+    }
+}
+
+impl Cell<Text> for RegistrationEventKind {
+    fn append(&self, builder: &mut StringBuilder) -> anyhow::Result<()> {
+        builder.append_value(self.as_str());
+        Ok(())
+    }
+}
+
+crate::columnar_row!(RegistrationEventRecord {
+    id: Text,
+    kind: Text,
+    domain: DomainId,
+    name: Text,
+    registrant: AccountId,
+    block_number: U32,
+    triggered_date: U64,
+    transaction_id: Hash,
+    cost: Wei,
+    old_expiry: U64,
+    new_expiry: U64,
+    old_capacity: U64,
+    new_capacity: U64,
+});
+
+impl Columnar for Vec<RegistrationEventRecord> {
+    fn schema() -> Schema {
+        columnar::schema::<RegistrationEventRecord>()
+    }
+
+    fn to_batch(&self) -> anyhow::Result<RecordBatch> {
+        columnar::to_batch(self)
+    }
+}
+
+pub struct RegistrationEventsBuilder;
+
+impl RegistrationEventsBuilder {
+    fn build_query_filtered(
+        offset: i32,
+        block: i32,
+        filter: queries::RegistrationEventFilter,
+    ) -> cynic::Operation<queries::QueryRegistrationEvents, queries::QueryRegistrationEventsVariables>
+    {
+        <queries::QueryRegistrationEvents as cynic::QueryBuilder>::build(
+            queries::QueryRegistrationEventsVariables {
+                skip: Some(offset),
+                block: Some(BlockHeight::number(block)),
+                filter: Some(filter),
+            },
+        )
+    }
+
+    /// Registration events of the domain with id `domain`, at `block`.
+    pub async fn query_domain(domain: &str, block: i32) -> Vec<RegistrationEventRecord> {
+        let filter = queries::RegistrationEventFilter {
+            registration_: Some(queries::RegistrationFilter {
+                domain: Some(domain.to_lowercase()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        query_pages(|offset| Self::build_query_filtered(offset, block, filter.clone())).await
+    }
+
+    /// Registration events of every domain in `domains`, at `block`.
+    pub async fn query_domains(domains: &[String], block: i32) -> Vec<RegistrationEventRecord> {
+        if domains.is_empty() {
+            return Vec::new();
+        }
+        let filter = queries::RegistrationEventFilter {
+            registration_: Some(queries::RegistrationFilter {
+                domain_in: Some(domains.iter().map(|d| d.to_lowercase()).collect()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        query_pages(|offset| Self::build_query_filtered(offset, block, filter.clone())).await
+    }
+
+    /// Registration events sent by `registrant`, at `block`.
+    pub async fn query_registrant(registrant: &str, block: i32) -> Vec<RegistrationEventRecord> {
+        let filter = queries::RegistrationEventFilter {
+            registrant: Some(registrant.to_lowercase()),
+            ..Default::default()
+        };
+
+        query_pages(|offset| Self::build_query_filtered(offset, block, filter.clone())).await
+    }
+
+    /// Registration events in any block after `after`, up to `block`.
+    pub async fn query_after(after: i32, block: i32) -> Vec<RegistrationEventRecord> {
+        let filter = queries::RegistrationEventFilter {
+            block_number_gt: Some(after),
+            ..Default::default()
+        };
+
+        query_pages(|offset| Self::build_query_filtered(offset, block, filter.clone())).await
+    }
+}
+
+impl BuildQuery for RegistrationEventsBuilder {
+    type Vars = queries::QueryRegistrationEventsVariables;
+
+    type ResponseData = queries::QueryRegistrationEvents;
+
+    fn build_query(offset: i32, block: i32) -> cynic::Operation<Self::ResponseData, Self::Vars> {
+        Self::build_query_filtered(offset, block, Default::default())
+    }
+}
+
+impl IsFull for queries::QueryRegistrationEvents {
+    type Item = RegistrationEventRecord;
+
+    fn len(&self) -> usize {
+        self.registration_events.len()
+    }
+
+    fn into_iter(self) -> impl IntoIterator<Item = Self::Item> {
+        IntoIterator::into_iter(self.registration_events).filter_map(|event| {
+            use queries::RegistrationEvent::*;
+
+            let (kind, id, block_number, triggered_date, transaction_id, registrant, domain) =
+                match &event {
+                    NameRegistered(e) => (
+                        RegistrationEventKind::NameRegistered,
+                        &e.id,
+                        e.block_number,
+                        &e.triggered_date,
+                        &e.transaction_id,
+                        &e.registrant,
+                        &e.registration.domain,
+                    ),
+                    NameRenewed(e) => (
+                        RegistrationEventKind::NameRenewed,
+                        &e.id,
+                        e.block_number,
+                        &e.triggered_date,
+                        &e.transaction_id,
+                        &e.registrant,
+                        &e.registration.domain,
+                    ),
+                    CapacityUpdated(e) => (
+                        RegistrationEventKind::CapacityUpdated,
+                        &e.id,
+                        e.block_number,
+                        &e.triggered_date,
+                        &e.transaction_id,
+                        &e.registrant,
+                        &e.registration.domain,
+                    ),
+                    Unknown => return None,
+                };
+
+            let mut record = RegistrationEventRecord {
+                id: id.inner().to_string(),
+                kind,
+                domain: domain.id.0.handle_id::<DOMAIN_ID_LEN>(),
+                name: domain.name.clone(),
+                registrant: registrant.id.0.handle_id::<ACCOUNT_ID_LEN>(),
+                block_number,
+                triggered_date: triggered_date.0.parse().unwrap(),
+                transaction_id: transaction_id.0.clone(),
+                cost: None,
+                old_expiry: None,
+                new_expiry: None,
+                old_capacity: None,
+                new_capacity: None,
+            };
+
+            match event {
+                NameRegistered(e) => {
+                    record.cost = e.cost.map(|cost| cost.0);
+                    record.new_expiry = e.expiry_date.0.parse().ok();
+                }
+                NameRenewed(e) => {
+                    record.cost = e.cost.map(|cost| cost.0);
+                    record.new_expiry = e.expiry_date.0.parse().ok();
+                }
+                CapacityUpdated(e) => {
+                    record.new_capacity = e.capacity.0.parse().ok();
+                }
+                Unknown => unreachable!(),
+            }
+
+            Some(record)
+        })
+    }
+}
+
+/// Group events per domain and carry expiry and capacity forward so every
+/// event knows the value it replaced. `events` must be in block order.
+pub fn histories(events: Vec<RegistrationEventRecord>) -> Vec<RegistrationHistory> {
+    let mut res = BTreeMap::<String, RegistrationHistory>::new();
+    let mut state = HashMap::<String, (Option<i64>, Option<i64>)>::new();
+
+    for mut event in events {
+        let (expiry, capacity) = state.entry(event.domain.clone()).or_default();
+
+        match event.kind {
+            RegistrationEventKind::NameRegistered | RegistrationEventKind::NameRenewed => {
+                event.old_expiry = *expiry;
+                *expiry = event.new_expiry;
+            }
+            RegistrationEventKind::CapacityUpdated => {
+                event.old_capacity = *capacity;
+                *capacity = event.new_capacity;
+            }
+        }
+
+        res.entry(event.domain.clone())
+            .or_insert_with(|| RegistrationHistory {
+                domain: event.domain.clone(),
+                name: event.name.clone(),
+                events: Vec::new(),
+            })
+            .events
+            .push(event);
+    }
+
+    res.into_values().collect()
+}
@@ -1,7 +1,7 @@
 /*!
 ```
-query QueryRegistrations($skip: Int = 10) {
-  registrations(skip: $skip, first: 1000) {
+query QueryRegistrations($skip: Int = 10, $block: Block_height, $filter: Registration_filter) {
+  registrations(skip: $skip, first: 1000, block: $block, where: $filter) {
     expiryDate
     origin {
       id
@@ -17,24 +17,36 @@ query QueryRegistrations($skip: Int = 10) {
 */
 #[cynic::schema_for_derives(file = r#"schema.gql"#, module = "schema")]
 mod queries {
-    use super::schema;
+    use crate::{
+        fragments::{BigInt, BlockChangedFilter, BlockHeight, Bytes, DomainId},
+        schema,
+    };
 
     #[derive(cynic::QueryVariables, Debug)]
     pub struct QueryRegistrationsVariables {
         pub skip: Option<i32>,
+        pub block: Option<BlockHeight>,
+        pub filter: Option<RegistrationFilter>,
+    }
+
+    #[derive(cynic::InputObject, Debug, Default, Clone)]
+    #[cynic(graphql_type = "Registration_filter")]
+    pub struct RegistrationFilter {
+        #[cynic(rename = "_change_block", skip_serializing_if = "Option::is_none")]
+        pub change_block: Option<BlockChangedFilter>,
     }
 
     #[derive(cynic::QueryFragment, Debug)]
     #[cynic(graphql_type = "Query", variables = "QueryRegistrationsVariables")]
     pub struct QueryRegistrations {
-        #[arguments(skip: $skip, first: 1000)]
+        #[arguments(skip: $skip, first: 1000, block: $block, where: $filter)]
         pub registrations: Vec<Registration>,
     }
 
     #[derive(cynic::QueryFragment, Debug)]
     pub struct Registration {
         pub expiry_date: Option<BigInt>,
-        pub origin: Option<Domain>,
+        pub origin: Option<DomainId>,
         pub capacity: Option<BigInt>,
         pub domain: Domain2,
     }
@@ -45,33 +57,24 @@ mod queries {
         pub subdomain_count: i32,
         pub id: Bytes,
     }
-
-    #[derive(cynic::QueryFragment, Debug)]
-    pub struct Domain {
-        pub id: Bytes,
-    }
-
-    #[derive(cynic::Scalar, Debug, Clone)]
-    pub struct BigInt(pub String);
-
-    #[derive(cynic::Scalar, Debug, Clone)]
-    pub struct Bytes(pub String);
-}
-
-#[allow(non_snake_case, non_camel_case_types)]
-mod schema {
-    cynic::use_schema!(r#"schema.gql"#);
 }
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
-use serde::Serialize;
+use arrow_array::RecordBatch;
+use arrow_schema::Schema;
+use serde::{Deserialize, Serialize};
 
-use crate::{BuildQuery, IsFull, DOMAIN_ID_LEN};
+use crate::{
+    columnar::{self, Columnar, DomainId, U32, U64},
+    fragments::{BlockChangedFilter, BlockHeight},
+    output::Tabular,
+    query_pages, BuildQuery, IsFull, DOMAIN_ID_LEN,
+};
 
 use self::queries::Domain2;
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Record {
     pub origin: String,
     pub expire: Option<i64>,
@@ -79,20 +82,93 @@ pub struct Record {
     pub children: i32,
 }
 
-#[derive(Debug, Serialize)]
-pub struct Records(pub HashMap<String, Record>);
+/// Keyed by domain id, so rows come out in a stable order.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Records(pub BTreeMap<String, Record>);
+
+#[derive(Serialize)]
+pub struct RecordRow<'a> {
+    pub id: &'a str,
+    pub origin: &'a str,
+    pub expire: Option<i64>,
+    pub capacity: i64,
+    pub children: i32,
+}
+
+impl<'a> RecordRow<'a> {
+    pub fn new(id: &'a str, record: &'a Record) -> Self {
+        Self {
+            id,
+            origin: &record.origin,
+            expire: record.expire,
+            capacity: record.capacity,
+            children: record.children,
+        }
+    }
+}
+
+impl Tabular for Records {
+    type Row<'a> = RecordRow<'a>;
+
+    fn rows(&self) -> impl Iterator<Item = Self::Row<'_>> {
+        self.0.iter().map(|(id, record)| RecordRow::new(id, record))
+    }
+}
+
+crate::columnar_row!(RecordRow<'_> {
+    id: DomainId,
+    origin: DomainId,
+    expire: U64,
+    capacity: U64,
+    children: U32,
+});
+
+impl Columnar for Records {
+    fn schema() -> Schema {
+        columnar::schema::<RecordRow>()
+    }
+
+    fn to_batch(&self) -> anyhow::Result<RecordBatch> {
+        columnar::to_batch(&self.rows().collect::<Vec<_>>())
+    }
+}
 
 pub struct RecordsBuilder;
 
+impl RecordsBuilder {
+    fn build_query_filtered(
+        offset: i32,
+        block: i32,
+        filter: queries::RegistrationFilter,
+    ) -> cynic::Operation<queries::QueryRegistrations, queries::QueryRegistrationsVariables> {
+        <queries::QueryRegistrations as cynic::QueryBuilder>::build(
+            queries::QueryRegistrationsVariables {
+                skip: Some(offset),
+                block: Some(BlockHeight::number(block)),
+                filter: Some(filter),
+            },
+        )
+    }
+
+    /// Registrations written in any block after `after`, as they are at
+    /// `block`. `children` is only current for these, a new subdomain
+    /// changes the domain but not its registration.
+    pub async fn query_changed(after: i32, block: i32) -> Vec<(String, Record)> {
+        let filter = queries::RegistrationFilter {
+            change_block: Some(BlockChangedFilter::after(after)),
+        };
+
+        query_pages(|offset| Self::build_query_filtered(offset, block, filter.clone())).await
+    }
+}
+
 impl BuildQuery for RecordsBuilder {
     type Vars = queries::QueryRegistrationsVariables;
 
     type ResponseData = queries::QueryRegistrations;
 
-    fn build_query(offset: i32) -> cynic::Operation<Self::ResponseData, Self::Vars> {
-        <queries::QueryRegistrations as cynic::QueryBuilder>::build(
-            queries::QueryRegistrationsVariables { skip: Some(offset) },
-        )
+    fn build_query(offset: i32, block: i32) -> cynic::Operation<Self::ResponseData, Self::Vars> {
+        Self::build_query_filtered(offset, block, Default::default())
     }
 }
 
@@ -0,0 +1,1086 @@
+/*!
+```
+query QueryDomainResolvers($skip: Int = 10, $block: Block_height, $filter: Domain_filter) {
+  domains(skip: $skip, first: 1000, block: $block, where: $filter) {
+    id
+    name
+    resolver {
+      address
+      contentHash
+      texts
+      coinTypes
+      addr {
+        id
+      }
+    }
+  }
+}
+
+query QueryChangedResolvers($skip: Int = 10, $block: Block_height, $filter: Resolver_filter) {
+  resolvers(skip: $skip, first: 1000, block: $block, where: $filter) {
+    address
+    contentHash
+    texts
+    coinTypes
+    addr {
+      id
+    }
+    domain {
+      id
+    }
+  }
+}
+
+query QueryNewResolvers($skip: Int = 10, $block: Block_height, $filter: NewResolver_filter) {
+  newResolvers(skip: $skip, first: 1000, block: $block, where: $filter, orderBy: blockNumber, orderDirection: asc) {
+    id
+    blockNumber
+    domain {
+      id
+    }
+  }
+}
+
+query QuerySets($skip: Int = 10, $block: Block_height, $filter: Set_filter) {
+  sets(skip: $skip, first: 1000, block: $block, where: $filter, orderBy: blockNumber, orderDirection: asc) {
+    id
+    keyHash
+    value
+    blockNumber
+    domain {
+      id
+    }
+  }
+}
+
+query QuerySetLinks($skip: Int = 10, $block: Block_height, $filter: SetLink_filter) {
+  setLinks(skip: $skip, first: 1000, block: $block, where: $filter, orderBy: blockNumber, orderDirection: asc) {
+    id
+    keyHash
+    value
+    blockNumber
+    domain {
+      id
+    }
+  }
+}
+```
+*/
+#[cynic::schema_for_derives(file = r#"schema.gql"#, module = "schema")]
+mod queries {
+    use crate::{
+        fragments::{
+            AccountFields, BigInt, BlockChangedFilter, BlockHeight, Bytes, DomainFilter, DomainId,
+        },
+        schema,
+    };
+
+    #[derive(cynic::QueryVariables, Debug)]
+    pub struct QueryDomainResolversVariables {
+        pub skip: Option<i32>,
+        pub block: Option<BlockHeight>,
+        pub filter: Option<DomainFilter>,
+    }
+
+    #[derive(cynic::QueryVariables, Debug)]
+    pub struct QueryChangedResolversVariables {
+        pub skip: Option<i32>,
+        pub block: Option<BlockHeight>,
+        pub filter: Option<ResolverFilter>,
+    }
+
+    #[derive(cynic::QueryVariables, Debug)]
+    pub struct QueryNewResolversVariables {
+        pub skip: Option<i32>,
+        pub block: Option<BlockHeight>,
+        pub filter: Option<NewResolverFilter>,
+    }
+
+    #[derive(cynic::QueryVariables, Debug)]
+    pub struct QuerySetsVariables {
+        pub skip: Option<i32>,
+        pub block: Option<BlockHeight>,
+        pub filter: Option<SetFilter>,
+    }
+
+    #[derive(cynic::QueryVariables, Debug)]
+    pub struct QuerySetLinksVariables {
+        pub skip: Option<i32>,
+        pub block: Option<BlockHeight>,
+        pub filter: Option<SetLinkFilter>,
+    }
+
+    #[derive(cynic::InputObject, Debug, Clone)]
+    #[cynic(graphql_type = "Resolver_filter")]
+    pub struct ResolverFilter {
+        #[cynic(rename = "_change_block")]
+        pub change_block: BlockChangedFilter,
+    }
+
+    #[derive(cynic::InputObject, Debug, Default, Clone)]
+    #[cynic(graphql_type = "NewResolver_filter")]
+    pub struct NewResolverFilter {
+        #[cynic(rename = "blockNumber_gt", skip_serializing_if = "Option::is_none")]
+        pub block_number_gt: Option<i32>,
+    }
+
+    #[derive(cynic::InputObject, Debug, Default, Clone)]
+    #[cynic(graphql_type = "Set_filter")]
+    pub struct SetFilter {
+        #[cynic(rename = "blockNumber_gt", skip_serializing_if = "Option::is_none")]
+        pub block_number_gt: Option<i32>,
+    }
+
+    #[derive(cynic::InputObject, Debug, Default, Clone)]
+    #[cynic(graphql_type = "SetLink_filter")]
+    pub struct SetLinkFilter {
+        #[cynic(rename = "blockNumber_gt", skip_serializing_if = "Option::is_none")]
+        pub block_number_gt: Option<i32>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    #[cynic(graphql_type = "Query", variables = "QueryDomainResolversVariables")]
+    pub struct QueryDomainResolvers {
+        #[arguments(skip: $skip, first: 1000, block: $block, where: $filter)]
+        pub domains: Vec<Domain>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    #[cynic(graphql_type = "Query", variables = "QueryChangedResolversVariables")]
+    pub struct QueryChangedResolvers {
+        #[arguments(skip: $skip, first: 1000, block: $block, where: $filter)]
+        pub resolvers: Vec<ChangedResolver>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    #[cynic(graphql_type = "Query", variables = "QueryNewResolversVariables")]
+    pub struct QueryNewResolvers {
+        #[arguments(skip: $skip, first: 1000, block: $block, where: $filter, orderBy: blockNumber, orderDirection: asc)]
+        pub new_resolvers: Vec<NewResolver>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    #[cynic(graphql_type = "Query", variables = "QuerySetsVariables")]
+    pub struct QuerySets {
+        #[arguments(skip: $skip, first: 1000, block: $block, where: $filter, orderBy: blockNumber, orderDirection: asc)]
+        pub sets: Vec<Set>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    #[cynic(graphql_type = "Query", variables = "QuerySetLinksVariables")]
+    pub struct QuerySetLinks {
+        #[arguments(skip: $skip, first: 1000, block: $block, where: $filter, orderBy: blockNumber, orderDirection: asc)]
+        pub set_links: Vec<SetLink>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    pub struct Domain {
+        pub id: Bytes,
+        pub name: Option<String>,
+        pub resolver: Option<Resolver>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    pub struct Resolver {
+        pub address: Bytes,
+        pub content_hash: Option<Bytes>,
+        pub texts: Option<Vec<String>>,
+        pub coin_types: Option<Vec<BigInt>>,
+        pub addr: Option<AccountFields>,
+    }
+
+    /// A [`Resolver`] found by its own changes, with the domain it serves.
+    #[derive(cynic::QueryFragment, Debug)]
+    #[cynic(graphql_type = "Resolver")]
+    pub struct ChangedResolver {
+        pub address: Bytes,
+        pub content_hash: Option<Bytes>,
+        pub texts: Option<Vec<String>>,
+        pub coin_types: Option<Vec<BigInt>>,
+        pub addr: Option<AccountFields>,
+        pub domain: Option<DomainId>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    pub struct NewResolver {
+        pub id: cynic::Id,
+        pub block_number: i32,
+        pub domain: DomainId,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    pub struct Set {
+        pub id: cynic::Id,
+        pub key_hash: BigInt,
+        pub value: String,
+        pub block_number: i32,
+        pub domain: DomainId,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    pub struct SetLink {
+        pub id: cynic::Id,
+        pub key_hash: BigInt,
+        pub value: BigInt,
+        pub block_number: i32,
+        pub domain: DomainId,
+    }
+}
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    event_order,
+    fragments::{BlockChangedFilter, BlockHeight, DomainFilter},
+    query_all_at, query_pages, BuildQuery, HandleId, IsFull, ACCOUNT_ID_LEN, DOMAIN_ID_LEN,
+};
+
+/// Current resolver state of one domain.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolverRecord {
+    pub domain: String,
+    pub name: Option<String>,
+    pub resolver: String,
+    pub addr: Option<String>,
+    pub coin_types: Vec<String>,
+    /// Text keys the resolver lists, which name the hashes of `texts`.
+    pub keys: Vec<String>,
+    pub texts: Vec<KeyRecord>,
+    pub links: Vec<KeyRecord>,
+    pub content_hash: Option<String>,
+    /// `ipfs://`, `ipns://` or `bzz://` form of `content_hash`, when recognized.
+    pub content_uri: Option<String>,
+}
+
+/// Latest value set for one key. `key` is only known when the key hash
+/// matches one of the resolver's `keys`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyRecord {
+    pub key: Option<String>,
+    pub key_hash: String,
+    pub value: String,
+    pub block_number: i32,
+}
+
+/// One `Set`/`SetLink` event, as `(id, domain, record)`.
+type KeyEvent = (String, String, KeyRecord);
+
+/// One `NewResolver` event, as `(id, domain, block_number)`.
+type Switch = (String, String, i32);
+
+/// Resolver state and events to fold into the records, see [`merge`].
+#[derive(Default)]
+struct ResolverChanges {
+    /// Domains with their current resolver, `None` when it is unset.
+    domains: Vec<(String, Option<String>, Option<queries::Resolver>)>,
+    /// Resolvers changed without their domain, with the domain they serve.
+    resolvers: Vec<(String, queries::Resolver)>,
+    switches: Vec<Switch>,
+    texts: Vec<KeyEvent>,
+    links: Vec<KeyEvent>,
+}
+
+struct DomainResolversBuilder;
+struct ChangedResolversBuilder;
+struct NewResolversBuilder;
+struct SetsBuilder;
+struct SetLinksBuilder;
+
+impl DomainResolversBuilder {
+    fn build_query_filtered(
+        offset: i32,
+        block: i32,
+        filter: DomainFilter,
+    ) -> cynic::Operation<queries::QueryDomainResolvers, queries::QueryDomainResolversVariables>
+    {
+        <queries::QueryDomainResolvers as cynic::QueryBuilder>::build(
+            queries::QueryDomainResolversVariables {
+                skip: Some(offset),
+                block: Some(BlockHeight::number(block)),
+                filter: Some(filter),
+            },
+        )
+    }
+
+    /// Domains written in any block after `after`, as they are at `block`.
+    async fn query_changed(
+        after: i32,
+        block: i32,
+    ) -> Vec<<queries::QueryDomainResolvers as IsFull>::Item> {
+        let filter = DomainFilter {
+            change_block: Some(BlockChangedFilter::after(after)),
+            ..Default::default()
+        };
+
+        query_pages(|offset| Self::build_query_filtered(offset, block, filter.clone())).await
+    }
+}
+
+impl ChangedResolversBuilder {
+    /// Resolvers written in any block after `after`, as they are at `block`.
+    async fn query_changed(after: i32, block: i32) -> Vec<(String, queries::Resolver)> {
+        let filter = queries::ResolverFilter {
+            change_block: BlockChangedFilter::after(after),
+        };
+
+        query_pages(|offset| {
+            <queries::QueryChangedResolvers as cynic::QueryBuilder>::build(
+                queries::QueryChangedResolversVariables {
+                    skip: Some(offset),
+                    block: Some(BlockHeight::number(block)),
+                    filter: Some(filter.clone()),
+                },
+            )
+        })
+        .await
+    }
+}
+
+impl NewResolversBuilder {
+    fn build_query_filtered(
+        offset: i32,
+        block: i32,
+        filter: queries::NewResolverFilter,
+    ) -> cynic::Operation<queries::QueryNewResolvers, queries::QueryNewResolversVariables> {
+        <queries::QueryNewResolvers as cynic::QueryBuilder>::build(
+            queries::QueryNewResolversVariables {
+                skip: Some(offset),
+                block: Some(BlockHeight::number(block)),
+                filter: Some(filter),
+            },
+        )
+    }
+
+    /// Switches in any block after `after`, up to `block`.
+    async fn query_after(after: i32, block: i32) -> Vec<Switch> {
+        let filter = queries::NewResolverFilter {
+            block_number_gt: Some(after),
+        };
+
+        query_pages(|offset| Self::build_query_filtered(offset, block, filter.clone())).await
+    }
+}
+
+impl SetsBuilder {
+    fn build_query_filtered(
+        offset: i32,
+        block: i32,
+        filter: queries::SetFilter,
+    ) -> cynic::Operation<queries::QuerySets, queries::QuerySetsVariables> {
+        <queries::QuerySets as cynic::QueryBuilder>::build(queries::QuerySetsVariables {
+            skip: Some(offset),
+            block: Some(BlockHeight::number(block)),
+            filter: Some(filter),
+        })
+    }
+
+    /// `Set` events in any block after `after`, up to `block`.
+    async fn query_after(after: i32, block: i32) -> Vec<KeyEvent> {
+        let filter = queries::SetFilter {
+            block_number_gt: Some(after),
+        };
+
+        query_pages(|offset| Self::build_query_filtered(offset, block, filter.clone())).await
+    }
+}
+
+impl SetLinksBuilder {
+    fn build_query_filtered(
+        offset: i32,
+        block: i32,
+        filter: queries::SetLinkFilter,
+    ) -> cynic::Operation<queries::QuerySetLinks, queries::QuerySetLinksVariables> {
+        <queries::QuerySetLinks as cynic::QueryBuilder>::build(queries::QuerySetLinksVariables {
+            skip: Some(offset),
+            block: Some(BlockHeight::number(block)),
+            filter: Some(filter),
+        })
+    }
+
+    /// `SetLink` events in any block after `after`, up to `block`.
+    async fn query_after(after: i32, block: i32) -> Vec<KeyEvent> {
+        let filter = queries::SetLinkFilter {
+            block_number_gt: Some(after),
+        };
+
+        query_pages(|offset| Self::build_query_filtered(offset, block, filter.clone())).await
+    }
+}
+
+impl BuildQuery for DomainResolversBuilder {
+    type Vars = queries::QueryDomainResolversVariables;
+
+    type ResponseData = queries::QueryDomainResolvers;
+
+    fn build_query(offset: i32, block: i32) -> cynic::Operation<Self::ResponseData, Self::Vars> {
+        Self::build_query_filtered(offset, block, Default::default())
+    }
+}
+
+impl BuildQuery for NewResolversBuilder {
+    type Vars = queries::QueryNewResolversVariables;
+
+    type ResponseData = queries::QueryNewResolvers;
+
+    fn build_query(offset: i32, block: i32) -> cynic::Operation<Self::ResponseData, Self::Vars> {
+        Self::build_query_filtered(offset, block, Default::default())
+    }
+}
+
+impl BuildQuery for SetsBuilder {
+    type Vars = queries::QuerySetsVariables;
+
+    type ResponseData = queries::QuerySets;
+
+    fn build_query(offset: i32, block: i32) -> cynic::Operation<Self::ResponseData, Self::Vars> {
+        Self::build_query_filtered(offset, block, Default::default())
+    }
+}
+
+impl BuildQuery for SetLinksBuilder {
+    type Vars = queries::QuerySetLinksVariables;
+
+    type ResponseData = queries::QuerySetLinks;
+
+    fn build_query(offset: i32, block: i32) -> cynic::Operation<Self::ResponseData, Self::Vars> {
+        Self::build_query_filtered(offset, block, Default::default())
+    }
+}
+
+impl IsFull for queries::QueryDomainResolvers {
+    type Item = (String, Option<String>, Option<queries::Resolver>);
+
+    fn len(&self) -> usize {
+        self.domains.len()
+    }
+
+    fn into_iter(self) -> impl IntoIterator<Item = Self::Item> {
+        IntoIterator::into_iter(self.domains).map(|domain| {
+            (
+                domain.id.0.handle_id::<DOMAIN_ID_LEN>(),
+                domain.name,
+                domain.resolver,
+            )
+        })
+    }
+}
+
+impl IsFull for queries::QueryChangedResolvers {
+    /// Resolvers no domain points at are skipped.
+    type Item = (String, queries::Resolver);
+
+    fn len(&self) -> usize {
+        self.resolvers.len()
+    }
+
+    fn into_iter(self) -> impl IntoIterator<Item = Self::Item> {
+        IntoIterator::into_iter(self.resolvers).filter_map(|resolver| {
+            Some((
+                resolver.domain?.id.0.handle_id::<DOMAIN_ID_LEN>(),
+                queries::Resolver {
+                    address: resolver.address,
+                    content_hash: resolver.content_hash,
+                    texts: resolver.texts,
+                    coin_types: resolver.coin_types,
+                    addr: resolver.addr,
+                },
+            ))
+        })
+    }
+}
+
+impl IsFull for queries::QueryNewResolvers {
+    type Item = Switch;
+
+    fn len(&self) -> usize {
+        self.new_resolvers.len()
+    }
+
+    fn into_iter(self) -> impl IntoIterator<Item = Self::Item> {
+        IntoIterator::into_iter(self.new_resolvers).map(|event| {
+            (
+                event.id.into_inner(),
+                event.domain.id.0.handle_id::<DOMAIN_ID_LEN>(),
+                event.block_number,
+            )
+        })
+    }
+}
+
+impl IsFull for queries::QuerySets {
+    type Item = KeyEvent;
+
+    fn len(&self) -> usize {
+        self.sets.len()
+    }
+
+    fn into_iter(self) -> impl IntoIterator<Item = Self::Item> {
+        IntoIterator::into_iter(self.sets).map(|set| {
+            (
+                set.id.into_inner(),
+                set.domain.id.0.handle_id::<DOMAIN_ID_LEN>(),
+                KeyRecord {
+                    key: None,
+                    key_hash: set.key_hash.0,
+                    value: set.value,
+                    block_number: set.block_number,
+                },
+            )
+        })
+    }
+}
+
+impl IsFull for queries::QuerySetLinks {
+    type Item = KeyEvent;
+
+    fn len(&self) -> usize {
+        self.set_links.len()
+    }
+
+    fn into_iter(self) -> impl IntoIterator<Item = Self::Item> {
+        IntoIterator::into_iter(self.set_links).map(|link| {
+            (
+                link.id.into_inner(),
+                link.domain.id.0.handle_id::<DOMAIN_ID_LEN>(),
+                KeyRecord {
+                    key: None,
+                    key_hash: link.key_hash.0,
+                    value: link.value.0,
+                    block_number: link.block_number,
+                },
+            )
+        })
+    }
+}
+
+/// Resolver records of every domain with a resolver, all at `block`.
+///
+/// Each domain's resolver is the one `Domain.resolver` points at. `Set` and
+/// `SetLink` events don't say which resolver emitted them, so those before
+/// the domain's last `NewResolver` are taken to belong to an earlier one and
+/// dropped.
+pub async fn query_resolver_records(block: i32) -> Vec<ResolverRecord> {
+    let changes = ResolverChanges {
+        domains: query_all_at::<DomainResolversBuilder>(block).await,
+        resolvers: Vec::new(),
+        switches: query_all_at::<NewResolversBuilder>(block).await,
+        texts: query_all_at::<SetsBuilder>(block).await,
+        links: query_all_at::<SetLinksBuilder>(block).await,
+    };
+
+    let mut records = BTreeMap::new();
+    merge(&mut records, changes);
+    records.into_values().collect()
+}
+
+/// Bring `previous`, the records at block `after`, to `block`. Only the
+/// domains and resolvers written after `after` and the events emitted since
+/// are fetched, as in [`query_resolver_records`].
+pub async fn query_resolver_records_since(
+    previous: Vec<ResolverRecord>,
+    after: i32,
+    block: i32,
+) -> Vec<ResolverRecord> {
+    let changes = ResolverChanges {
+        domains: DomainResolversBuilder::query_changed(after, block).await,
+        resolvers: ChangedResolversBuilder::query_changed(after, block).await,
+        switches: NewResolversBuilder::query_after(after, block).await,
+        texts: SetsBuilder::query_after(after, block).await,
+        links: SetLinksBuilder::query_after(after, block).await,
+    };
+
+    let mut records = previous
+        .into_iter()
+        .map(|record| (record.domain.clone(), record))
+        .collect();
+    merge(&mut records, changes);
+    records.into_values().collect()
+}
+
+impl ResolverRecord {
+    fn new(domain: String, resolver: String) -> Self {
+        Self {
+            domain,
+            name: None,
+            resolver,
+            addr: None,
+            coin_types: Vec::new(),
+            keys: Vec::new(),
+            texts: Vec::new(),
+            links: Vec::new(),
+            content_hash: None,
+            content_uri: None,
+        }
+    }
+
+    fn set_resolver(&mut self, resolver: queries::Resolver) {
+        let content_hash = resolver.content_hash.map(|hash| hash.0);
+
+        self.addr = resolver
+            .addr
+            .map(|addr| addr.id.0.handle_id::<ACCOUNT_ID_LEN>());
+        self.coin_types = resolver
+            .coin_types
+            .unwrap_or_default()
+            .into_iter()
+            .map(|coin| coin.0)
+            .collect();
+        self.keys = resolver.texts.unwrap_or_default();
+        self.content_uri = content_hash.as_deref().and_then(content_uri);
+        self.content_hash = content_hash;
+    }
+}
+
+/// Fold `changes` into `records`, keyed by domain. Events in `changes` must
+/// all come after those already folded in.
+fn merge(records: &mut BTreeMap<String, ResolverRecord>, changes: ResolverChanges) {
+    for (domain, name, resolver) in changes.domains {
+        let Some(resolver) = resolver else {
+            records.remove(&domain);
+            continue;
+        };
+        let address = resolver.address.0.clone().handle_id::<ACCOUNT_ID_LEN>();
+        let record = records
+            .entry(domain.clone())
+            .or_insert_with(|| ResolverRecord::new(domain.clone(), address.clone()));
+        if record.resolver != address {
+            *record = ResolverRecord::new(domain, address);
+        }
+        record.name = name;
+        record.set_resolver(resolver);
+    }
+
+    // a resolver the domain has since moved away from no longer applies
+    for (domain, resolver) in changes.resolvers {
+        let address = resolver.address.0.clone().handle_id::<ACCOUNT_ID_LEN>();
+        if let Some(record) = records.get_mut(&domain) {
+            if record.resolver == address {
+                record.set_resolver(resolver);
+            }
+        }
+    }
+
+    // the last switch of each domain, records set before it are dropped
+    let mut switched = HashMap::<&str, (i32, u64, &str)>::new();
+    for (id, domain, block_number) in changes.switches.iter() {
+        let order = event_order(*block_number, id);
+        let last = switched.entry(domain).or_insert(order);
+        *last = order.max(*last);
+    }
+    for domain in switched.keys() {
+        if let Some(record) = records.get_mut(*domain) {
+            record.texts.clear();
+            record.links.clear();
+        }
+    }
+
+    for (events, links) in [(changes.texts, false), (changes.links, true)] {
+        for (_, domain, event) in latest(events, records, &switched) {
+            let Some(record) = records.get_mut(&domain) else {
+                continue;
+            };
+            let list = if links {
+                &mut record.links
+            } else {
+                &mut record.texts
+            };
+            list.retain(|r| r.key_hash != event.key_hash);
+            list.push(event);
+        }
+    }
+
+    for record in records.values_mut() {
+        let hashes = record
+            .keys
+            .iter()
+            .map(|key| (key_hash(key), key))
+            .collect::<HashMap<_, _>>();
+
+        for text in record.texts.iter_mut().chain(record.links.iter_mut()) {
+            text.key = parse_u256(&text.key_hash)
+                .and_then(|hash| hashes.get(&hash))
+                .map(|key| key.to_string());
+        }
+        record.texts.sort_by(|a, b| a.key_hash.cmp(&b.key_hash));
+        record.links.sort_by(|a, b| a.key_hash.cmp(&b.key_hash));
+    }
+}
+
+/// The last event per `(domain, key_hash)` among those of domains with a
+/// record and after the domain's last resolver switch.
+fn latest(
+    events: Vec<KeyEvent>,
+    records: &BTreeMap<String, ResolverRecord>,
+    switched: &HashMap<&str, (i32, u64, &str)>,
+) -> Vec<KeyEvent> {
+    let mut latest = BTreeMap::<(String, String), KeyEvent>::new();
+    for event in events {
+        let (id, domain, record) = &event;
+        let order = event_order(record.block_number, id);
+        if !records.contains_key(domain)
+            || switched
+                .get(domain.as_str())
+                .is_some_and(|switch| order < *switch)
+        {
+            continue;
+        }
+
+        let key = (domain.clone(), record.key_hash.clone());
+        match latest.get(&key) {
+            Some((last_id, _, last)) if event_order(last.block_number, last_id) > order => {}
+            _ => {
+                latest.insert(key, event);
+            }
+        }
+    }
+    latest.into_values().collect()
+}
+
+/// `keccak256(key)`, the hash the resolver emits as `keyHash`.
+fn key_hash(key: &str) -> [u8; 32] {
+    use tiny_keccak::{Hasher, Keccak};
+
+    let mut hasher = Keccak::v256();
+    let mut out = [0u8; 32];
+    hasher.update(key.as_bytes());
+    hasher.finalize(&mut out);
+    out
+}
+
+/// Parse a decimal `BigInt` into big-endian uint256 bytes.
+fn parse_u256(decimal: &str) -> Option<[u8; 32]> {
+    let mut out = [0u8; 32];
+    for digit in decimal.chars() {
+        let mut carry = digit.to_digit(10)?;
+        for byte in out.iter_mut().rev() {
+            let v = *byte as u32 * 10 + carry;
+            *byte = v as u8;
+            carry = v >> 8;
+        }
+        if carry != 0 {
+            return None;
+        }
+    }
+    Some(out)
+}
+
+/// Decode an EIP-1577 content hash into a URI.
+pub fn content_uri(content_hash: &str) -> Option<String> {
+    let bytes = hex_decode(content_hash.strip_prefix("0x")?)?;
+
+    match bytes.as_slice() {
+        // ipfs-ns, CIDv1 dag-pb sha2-256: print as the familiar CIDv0
+        [0xe3, 0x01, 0x01, 0x70, cid @ ..] if cid.starts_with(&[0x12, 0x20]) => {
+            Some(format!("ipfs://{}", base58(cid)))
+        }
+        [0xe3, 0x01, cid @ ..] if !cid.is_empty() => Some(format!("ipfs://b{}", base32(cid))),
+        [0xe5, 0x01, cid @ ..] if !cid.is_empty() => Some(format!("ipns://b{}", base32(cid))),
+        // swarm-ns, CIDv1 swarm-manifest keccak-256
+        [0xe4, 0x01, 0x01, 0xfa, 0x01, 0x1b, 0x20, hash @ ..] if hash.len() == 32 => {
+            Some(format!("bzz://{}", hex_encode(hash)))
+        }
+        _ => None,
+    }
+}
+
+pub(crate) fn hex_decode(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+pub(crate) fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn base58(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+    let mut digits = Vec::<u8>::new();
+    for byte in bytes {
+        let mut carry = *byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+
+    let zeros = bytes.iter().take_while(|b| **b == 0).count();
+    std::iter::repeat_n('1', zeros)
+        .chain(digits.iter().rev().map(|d| ALPHABET[*d as usize] as char))
+        .collect()
+}
+
+/// RFC 4648 base32, lowercase and unpadded as used by multibase `b`.
+fn base32(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+    let mut res = String::new();
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for byte in bytes {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            res.push(ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+        buffer &= (1 << bits) - 1;
+    }
+    if bits > 0 {
+        res.push(ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use primitive_types::U256;
+
+    use super::*;
+    use crate::fragments::Bytes;
+
+    const DOMAIN: &str = "0x1111111111111111111111111111111111111111111111111111111111111111";
+    const RESOLVER: &str = "0x2222222222222222222222222222222222222222";
+
+    fn decimal_hash(key: &str) -> String {
+        U256::from_big_endian(&key_hash(key)).to_string()
+    }
+
+    fn resolver(texts: &[&str]) -> queries::Resolver {
+        queries::Resolver {
+            address: Bytes(RESOLVER.to_string()),
+            content_hash: None,
+            texts: Some(texts.iter().map(|key| key.to_string()).collect()),
+            coin_types: None,
+            addr: None,
+        }
+    }
+
+    fn set(id: &str, key: &str, value: &str, block_number: i32) -> KeyEvent {
+        (
+            id.to_string(),
+            DOMAIN.to_string(),
+            KeyRecord {
+                key: None,
+                key_hash: decimal_hash(key),
+                value: value.to_string(),
+                block_number,
+            },
+        )
+    }
+
+    #[test]
+    fn base58_keeps_leading_zeros() {
+        assert_eq!(base58(b""), "");
+        assert_eq!(base58(b"\0\0\x01"), "112");
+        assert_eq!(base58(b"Hello World!"), "2NEpo7TZRRrLZSi2U");
+    }
+
+    #[test]
+    fn base32_is_lowercase_and_unpadded() {
+        assert_eq!(base32(b""), "");
+        assert_eq!(base32(b"f"), "my");
+        assert_eq!(base32(b"fo"), "mzxq");
+        assert_eq!(base32(b"foo"), "mzxw6");
+        assert_eq!(base32(b"foobar"), "mzxw6ytboi");
+    }
+
+    #[test]
+    fn parse_u256_reads_decimal_up_to_the_max() {
+        assert_eq!(parse_u256("0"), Some([0; 32]));
+
+        let mut expected = [0; 32];
+        expected[30] = 1;
+        assert_eq!(parse_u256("256"), Some(expected));
+
+        assert_eq!(parse_u256(&U256::MAX.to_string()), Some([0xff; 32]));
+        assert_eq!(
+            parse_u256(&decimal_hash("avatar")),
+            Some(key_hash("avatar"))
+        );
+        assert_eq!(
+            parse_u256(
+                "115792089237316195423570985008687907853269984665640564039457584007913129639936"
+            ),
+            None
+        );
+        assert_eq!(parse_u256("12a"), None);
+    }
+
+    #[test]
+    fn content_uri_decodes_known_hashes() {
+        // the EIP-1577 examples
+        assert_eq!(
+            content_uri(
+                "0xe3010170122029f2d17be6139079dc48696d1f582a8530eb9805b561eda517e22a892c7e3f1f"
+            )
+            .as_deref(),
+            Some("ipfs://QmRAQB6YaCyidP37UdDnjFY5vQuiBrcqdyoW1CuDgwxkD4")
+        );
+        assert_eq!(
+            content_uri(
+                "0xe40101fa011b20d1de9994b4d039f6548d191eb26786769f580809256b4685ef316805265ea162"
+            )
+            .as_deref(),
+            Some("bzz://d1de9994b4d039f6548d191eb26786769f580809256b4685ef316805265ea162")
+        );
+        // raw codec ipfs and libp2p-key ipns stay CIDv1
+        assert_eq!(
+            content_uri(
+                "0xe3010155122029f2d17be6139079dc48696d1f582a8530eb9805b561eda517e22a892c7e3f1f"
+            )
+            .as_deref(),
+            Some("ipfs://bafkreibj6lixxzqtsb45ysdjnupvqkufgdvzqbnvmhw2kf7cfkesy7r7d4")
+        );
+        assert_eq!(
+            content_uri(
+                "0xe50101720024080112205cbd6e0f4fb4b3d28b1c8b7e2c4b6cc14a0ad2d3a4c34ab33fb3fe4b9c2c0b8c"
+            )
+            .as_deref(),
+            Some("ipns://bafzaajaiaejcaxf5nyhu7nft2kfrzc36frfwzqkkbljnhjgdjkzt7m76joocyc4m")
+        );
+        assert_eq!(content_uri("0x"), None);
+        assert_eq!(content_uri("0xe301"), None);
+        assert_eq!(content_uri("e3010170"), None);
+    }
+
+    #[test]
+    fn merge_takes_the_last_event_of_a_block_by_log_index() {
+        let mut records = BTreeMap::new();
+        merge(
+            &mut records,
+            ResolverChanges {
+                domains: vec![(
+                    DOMAIN.to_string(),
+                    Some("alice.dot".to_string()),
+                    Some(resolver(&["avatar", "url"])),
+                )],
+                texts: vec![
+                    set("100-12", "avatar", "second", 100),
+                    set("100-3", "avatar", "first", 100),
+                ],
+                ..Default::default()
+            },
+        );
+
+        let record = &records[DOMAIN];
+        assert_eq!(record.resolver, RESOLVER);
+        // `url` is listed but was never set
+        assert_eq!(record.texts.len(), 1);
+        assert_eq!(record.texts[0].key.as_deref(), Some("avatar"));
+        assert_eq!(record.texts[0].value, "second");
+    }
+
+    #[test]
+    fn merge_drops_records_set_before_the_last_resolver_switch() {
+        let mut records = BTreeMap::new();
+        merge(
+            &mut records,
+            ResolverChanges {
+                domains: vec![
+                    (DOMAIN.to_string(), None, Some(resolver(&["avatar"]))),
+                    (format!("0x{}", "33".repeat(32)), None, None),
+                ],
+                switches: vec![("95-1".to_string(), DOMAIN.to_string(), 95)],
+                texts: vec![
+                    set("90-0", "avatar", "old resolver", 90),
+                    set("95-0", "url", "same block, before the switch", 95),
+                    set("95-2", "avatar", "new resolver", 95),
+                ],
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(records.len(), 1);
+        let texts = &records[DOMAIN].texts;
+        assert_eq!(texts.len(), 1);
+        assert_eq!(texts[0].value, "new resolver");
+
+        // a later switch back to the same resolver drops them again
+        merge(
+            &mut records,
+            ResolverChanges {
+                switches: vec![("120-0".to_string(), DOMAIN.to_string(), 120)],
+                ..Default::default()
+            },
+        );
+        assert!(records[DOMAIN].texts.is_empty());
+    }
+
+    #[test]
+    fn merge_updates_records_from_changes_only() {
+        let mut records = BTreeMap::new();
+        merge(
+            &mut records,
+            ResolverChanges {
+                domains: vec![(DOMAIN.to_string(), None, Some(resolver(&["avatar"])))],
+                texts: vec![set("90-0", "avatar", "kept", 90)],
+                ..Default::default()
+            },
+        );
+
+        // the resolver gained a key, another one the domain doesn't use
+        // changed too
+        let other = queries::Resolver {
+            address: Bytes(format!("0x{}", "44".repeat(20))),
+            ..resolver(&[])
+        };
+        merge(
+            &mut records,
+            ResolverChanges {
+                resolvers: vec![
+                    (DOMAIN.to_string(), resolver(&["avatar", "url"])),
+                    (DOMAIN.to_string(), other),
+                ],
+                texts: vec![set("110-0", "url", "added", 110)],
+                ..Default::default()
+            },
+        );
+
+        let record = &records[DOMAIN];
+        assert_eq!(record.resolver, RESOLVER);
+        assert_eq!(record.keys, ["avatar", "url"]);
+        let texts = record
+            .texts
+            .iter()
+            .map(|t| (t.key.as_deref(), t.value.as_str()))
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(
+            texts,
+            BTreeMap::from([(Some("avatar"), "kept"), (Some("url"), "added")])
+        );
+
+        // the domain moving to another resolver starts over
+        merge(
+            &mut records,
+            ResolverChanges {
+                domains: vec![(
+                    DOMAIN.to_string(),
+                    None,
+                    Some(queries::Resolver {
+                        address: Bytes(format!("0x{}", "44".repeat(20))),
+                        ..resolver(&[])
+                    }),
+                )],
+                switches: vec![("120-0".to_string(), DOMAIN.to_string(), 120)],
+                ..Default::default()
+            },
+        );
+        assert!(records[DOMAIN].keys.is_empty());
+        assert!(records[DOMAIN].texts.is_empty());
+    }
+}
@@ -0,0 +1,179 @@
+//! Registrar revenue from `NameRegistered`/`NameRenewed` costs.
+//!
+//! Costs are summed as `U256` wei, the width of the contract's amounts, so
+//! sums are exact. Overflow and malformed costs are errors, not panics.
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use primitive_types::U256;
+use serde::Serialize;
+use time::OffsetDateTime;
+
+use crate::{
+    prices::CostCheck,
+    registration_events::{RegistrationEventKind, RegistrationEventRecord},
+};
+
+/// Decimals of the native token costs are paid in.
+pub const DECIMALS: u32 = 18;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+}
+
+impl FromStr for Period {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "day" => Ok(Period::Day),
+            "week" => Ok(Period::Week),
+            "month" => Ok(Period::Month),
+            _ => anyhow::bail!("unknown period `{s}`, expected day, week or month"),
+        }
+    }
+}
+
+impl Period {
+    /// `2022-11-08`, `2022-W45` or `2022-11` for the period containing `timestamp`.
+    pub fn bucket(self, timestamp: i64) -> String {
+        let date = OffsetDateTime::from_unix_timestamp(timestamp)
+            .unwrap()
+            .date();
+        match self {
+            Period::Day => format!(
+                "{}-{:02}-{:02}",
+                date.year(),
+                date.month() as u8,
+                date.day()
+            ),
+            Period::Week => {
+                let (year, week, _) = date.to_iso_week_date();
+                format!("{year}-W{week:02}")
+            }
+            Period::Month => format!("{}-{:02}", date.year(), date.month() as u8),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RevenueRow {
+    pub period: String,
+    pub kind: RegistrationEventKind,
+    pub name_length: usize,
+    pub count: usize,
+    pub cost_wei: String,
+    pub cost: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RevenueReport {
+    pub period: Period,
+    pub rows: Vec<RevenueRow>,
+    pub registered_wei: String,
+    pub registered: String,
+    pub renewed_wei: String,
+    pub renewed: String,
+    /// Filled in when checked against the price schedule, see
+    /// [`crate::prices::check_costs`].
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cost_mismatches: Vec<CostCheck>,
+}
+
+/// Sum costs per period, event kind and label length. Capacity updates and
+/// events without a cost are skipped.
+pub fn revenue(
+    events: &[RegistrationEventRecord],
+    period: Period,
+) -> anyhow::Result<RevenueReport> {
+    let mut buckets = BTreeMap::<(String, RegistrationEventKind, usize), (usize, U256)>::new();
+    let mut registered = U256::zero();
+    let mut renewed = U256::zero();
+
+    for event in events {
+        let total = match event.kind {
+            RegistrationEventKind::NameRegistered => &mut registered,
+            RegistrationEventKind::NameRenewed => &mut renewed,
+            RegistrationEventKind::CapacityUpdated => continue,
+        };
+        let Some(cost) = event.cost.as_deref().map(parse_wei).transpose()? else {
+            continue;
+        };
+
+        let key = (
+            period.bucket(event.triggered_date),
+            event.kind,
+            name_length(event.name.as_deref()),
+        );
+        let (count, sum) = buckets.entry(key).or_default();
+        *count += 1;
+        *sum = add_wei(*sum, cost)?;
+        *total = add_wei(*total, cost)?;
+    }
+
+    let rows = buckets
+        .into_iter()
+        .map(|((period, kind, name_length), (count, sum))| RevenueRow {
+            period,
+            kind,
+            name_length,
+            count,
+            cost_wei: sum.to_string(),
+            cost: Units(sum).to_string(),
+        })
+        .collect();
+
+    Ok(RevenueReport {
+        period,
+        rows,
+        registered_wei: registered.to_string(),
+        registered: Units(registered).to_string(),
+        renewed_wei: renewed.to_string(),
+        renewed: Units(renewed).to_string(),
+        cost_mismatches: Vec::new(),
+    })
+}
+
+/// Characters in the first label, `abc.dot` is 3. Unnamed domains count as 0.
+pub fn name_length(name: Option<&str>) -> usize {
+    name.and_then(|name| name.split('.').next())
+        .map(|label| label.chars().count())
+        .unwrap_or_default()
+}
+
+/// A decimal wei amount as returned by the subgraph.
+pub fn parse_wei(wei: &str) -> anyhow::Result<U256> {
+    U256::from_dec_str(wei).map_err(|e| anyhow::anyhow!("`{wei}` is not a wei amount: {e:?}"))
+}
+
+fn add_wei(a: U256, b: U256) -> anyhow::Result<U256> {
+    a.checked_add(b)
+        .ok_or_else(|| anyhow::anyhow!("wei sum overflows"))
+}
+
+/// Sum of wei amounts, failing rather than wrapping on overflow.
+pub fn sum_wei<'a>(amounts: impl IntoIterator<Item = &'a str>) -> anyhow::Result<U256> {
+    amounts
+        .into_iter()
+        .try_fold(U256::zero(), |sum, wei| add_wei(sum, parse_wei(wei)?))
+}
+
+/// Wei formatted in whole tokens with trailing zeros trimmed.
+pub struct Units(pub U256);
+
+impl fmt::Display for Units {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unit = U256::exp10(DECIMALS as usize);
+        let (whole, frac) = (self.0 / unit, self.0 % unit);
+        if frac.is_zero() {
+            return write!(f, "{whole}");
+        }
+        let frac = format!("{:0>width$}", frac.to_string(), width = DECIMALS as usize);
+        write!(f, "{whole}.{}", frac.trim_end_matches('0'))
+    }
+}
@@ -0,0 +1,269 @@
+/*!
+```
+query QuerySetNames($skip: Int = 10, $block: Block_height) {
+  setNames(skip: $skip, first: 1000, block: $block, orderBy: blockNumber, orderDirection: asc) {
+    blockNumber
+    triggeredDate
+    account {
+      id
+    }
+    tokenId {
+      id
+      name
+      owner {
+        id
+      }
+      resolvedAddress {
+        id
+      }
+    }
+  }
+}
+
+query QuerySetNftNames($skip: Int = 10, $block: Block_height) {
+  setNftNames(skip: $skip, first: 1000, block: $block, orderBy: blockNumber, orderDirection: asc) {
+    blockNumber
+    triggeredDate
+    nftTokenId
+    nftAddr {
+      id
+    }
+    domain {
+      id
+      name
+      createdAt
+    }
+  }
+}
+```
+*/
+#[cynic::schema_for_derives(file = r#"schema.gql"#, module = "schema")]
+mod queries {
+    use crate::{
+        fragments::{AccountFields, BigInt, BlockHeight, Bytes, DomainFields},
+        schema,
+    };
+
+    #[derive(cynic::QueryVariables, Debug)]
+    pub struct PageVariables {
+        pub skip: Option<i32>,
+        pub block: Option<BlockHeight>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    #[cynic(graphql_type = "Query", variables = "PageVariables")]
+    pub struct QuerySetNames {
+        #[arguments(skip: $skip, first: 1000, block: $block, orderBy: blockNumber, orderDirection: asc)]
+        pub set_names: Vec<SetName>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    #[cynic(graphql_type = "Query", variables = "PageVariables")]
+    pub struct QuerySetNftNames {
+        #[arguments(skip: $skip, first: 1000, block: $block, orderBy: blockNumber, orderDirection: asc)]
+        pub set_nft_names: Vec<SetNftName>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    pub struct SetName {
+        pub block_number: i32,
+        pub triggered_date: BigInt,
+        pub account: AccountFields,
+        pub token_id: Domain,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    pub struct Domain {
+        pub id: Bytes,
+        pub name: Option<String>,
+        pub owner: Option<AccountFields>,
+        pub resolved_address: Option<AccountFields>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    pub struct SetNftName {
+        pub block_number: i32,
+        pub triggered_date: BigInt,
+        pub nft_token_id: BigInt,
+        pub nft_addr: AccountFields,
+        pub domain: DomainFields,
+    }
+}
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::{
+    fragments::BlockHeight, query_all_at, BuildQuery, HandleId, IsFull, ACCOUNT_ID_LEN,
+    DOMAIN_ID_LEN,
+};
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ReverseStatus {
+    /// The account owns the name.
+    Owned,
+    /// Someone else owns the name but it resolves to the account.
+    Resolves,
+    /// Neither, the reverse record is stale.
+    Stale,
+}
+
+/// The latest `SetName` of an account, checked against the name's current
+/// owner and resolved address.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PrimaryName {
+    pub account: String,
+    pub domain: String,
+    pub name: Option<String>,
+    pub owner: Option<String>,
+    pub resolved_address: Option<String>,
+    pub status: ReverseStatus,
+    pub block_number: i32,
+    pub triggered_date: i64,
+}
+
+/// The latest `SetNftName` of a domain.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct NftName {
+    pub domain: String,
+    pub name: Option<String>,
+    pub nft_addr: String,
+    pub nft_token_id: String,
+    pub block_number: i32,
+    pub triggered_date: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReverseRecords {
+    pub primary_names_num: usize,
+    pub stale_num: usize,
+    pub primary_names: Vec<PrimaryName>,
+    pub nft_names_num: usize,
+    pub nft_names: Vec<NftName>,
+}
+
+pub struct SetNamesBuilder;
+pub struct SetNftNamesBuilder;
+
+impl BuildQuery for SetNamesBuilder {
+    type Vars = queries::PageVariables;
+
+    type ResponseData = queries::QuerySetNames;
+
+    fn build_query(offset: i32, block: i32) -> cynic::Operation<Self::ResponseData, Self::Vars> {
+        <queries::QuerySetNames as cynic::QueryBuilder>::build(page(offset, block))
+    }
+}
+
+impl BuildQuery for SetNftNamesBuilder {
+    type Vars = queries::PageVariables;
+
+    type ResponseData = queries::QuerySetNftNames;
+
+    fn build_query(offset: i32, block: i32) -> cynic::Operation<Self::ResponseData, Self::Vars> {
+        <queries::QuerySetNftNames as cynic::QueryBuilder>::build(page(offset, block))
+    }
+}
+
+fn page(offset: i32, block: i32) -> queries::PageVariables {
+    queries::PageVariables {
+        skip: Some(offset),
+        block: Some(BlockHeight::number(block)),
+    }
+}
+
+impl IsFull for queries::QuerySetNames {
+    type Item = PrimaryName;
+
+    fn len(&self) -> usize {
+        self.set_names.len()
+    }
+
+    fn into_iter(self) -> impl IntoIterator<Item = Self::Item> {
+        IntoIterator::into_iter(self.set_names).map(|set| {
+            let account = set.account.id.0.handle_id::<ACCOUNT_ID_LEN>();
+            let owner = set
+                .token_id
+                .owner
+                .map(|owner| owner.id.0.handle_id::<ACCOUNT_ID_LEN>());
+            let resolved_address = set
+                .token_id
+                .resolved_address
+                .map(|addr| addr.id.0.handle_id::<ACCOUNT_ID_LEN>());
+
+            let status = if owner.as_ref() == Some(&account) {
+                ReverseStatus::Owned
+            } else if resolved_address.as_ref() == Some(&account) {
+                ReverseStatus::Resolves
+            } else {
+                ReverseStatus::Stale
+            };
+
+            PrimaryName {
+                account,
+                domain: set.token_id.id.0.handle_id::<DOMAIN_ID_LEN>(),
+                name: set.token_id.name,
+                owner,
+                resolved_address,
+                status,
+                block_number: set.block_number,
+                triggered_date: set.triggered_date.0.parse().unwrap(),
+            }
+        })
+    }
+}
+
+impl IsFull for queries::QuerySetNftNames {
+    type Item = NftName;
+
+    fn len(&self) -> usize {
+        self.set_nft_names.len()
+    }
+
+    fn into_iter(self) -> impl IntoIterator<Item = Self::Item> {
+        IntoIterator::into_iter(self.set_nft_names).map(|set| NftName {
+            domain: set.domain.id.0.handle_id::<DOMAIN_ID_LEN>(),
+            name: set.domain.name,
+            nft_addr: set.nft_addr.id.0.handle_id::<ACCOUNT_ID_LEN>(),
+            nft_token_id: set.nft_token_id.0,
+            block_number: set.block_number,
+            triggered_date: set.triggered_date.0.parse().unwrap(),
+        })
+    }
+}
+
+/// Current primary name per account and bound NFT per domain, at `block`.
+/// Events arrive in block order, so the last one per key wins.
+pub async fn query_reverse_records(block: i32) -> ReverseRecords {
+    let primary_names = query_all_at::<SetNamesBuilder>(block)
+        .await
+        .into_iter()
+        .map(|name| (name.account.clone(), name))
+        .collect::<BTreeMap<_, _>>()
+        .into_values()
+        .collect::<Vec<_>>();
+
+    let nft_names = query_all_at::<SetNftNamesBuilder>(block)
+        .await
+        .into_iter()
+        .map(|name| (name.domain.clone(), name))
+        .collect::<BTreeMap<_, _>>()
+        .into_values()
+        .collect::<Vec<_>>();
+
+    ReverseRecords {
+        primary_names_num: primary_names.len(),
+        stale_num: primary_names
+            .iter()
+            .filter(|name| name.status == ReverseStatus::Stale)
+            .count(),
+        primary_names,
+        nft_names_num: nft_names.len(),
+        nft_names,
+    }
+}
@@ -0,0 +1,560 @@
+//! Every dataset at one block, written as a single SQLite database with
+//! normalized tables.
+//!
+//! `accounts` is derived from the addresses the other tables mention, the
+//! subgraph has no cheap way to list them all. `metadata` holds the
+//! [`Header`] of the snapshot. Its `checksum` covers the datasets but not
+//! `exported_at`, so two exports of the same block can be compared by it.
+//!
+//! [`Snapshot::query_since`] builds a snapshot from a previous one by only
+//! fetching what changed after its block.
+use std::collections::{BTreeMap, BTreeSet};
+
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    domains::{DomainRecord, DomainsBuilder},
+    envelope::{self, Header, FORMAT_VERSION},
+    meta::Meta,
+    new_subdomains::{NewSubdomain, NewSubdomainQueryBuilder},
+    output, query_all_at,
+    registrations::{Record, Records, RecordsBuilder},
+    resolvers::{self, KeyRecord, ResolverRecord},
+    transfers::{TransferFilter, TransferRecord, TransfersBuilder},
+};
+
+const SQLITE_MAGIC: &[u8] = b"SQLite format 3\0";
+
+const SCHEMA: &str = r#"
+CREATE TABLE metadata (
+    format_version INTEGER NOT NULL,
+    dataset TEXT NOT NULL,
+    endpoint TEXT NOT NULL,
+    deployment TEXT NOT NULL,
+    block_number INTEGER NOT NULL,
+    block_hash TEXT,
+    block_timestamp INTEGER,
+    -- JSON
+    params TEXT NOT NULL,
+    record_count INTEGER NOT NULL,
+    tool_version TEXT NOT NULL,
+    exported_at INTEGER NOT NULL,
+    checksum TEXT NOT NULL
+);
+
+CREATE TABLE accounts (
+    id TEXT PRIMARY KEY
+);
+
+CREATE TABLE domains (
+    id TEXT PRIMARY KEY,
+    name TEXT,
+    label_name TEXT,
+    labelhash TEXT,
+    parent TEXT,
+    owner TEXT,
+    resolved_address TEXT,
+    resolver TEXT,
+    subdomain_count INTEGER NOT NULL,
+    created_at INTEGER NOT NULL,
+    expire INTEGER,
+    capacity INTEGER
+);
+CREATE INDEX domains_name ON domains (name);
+CREATE INDEX domains_parent ON domains (parent);
+CREATE INDEX domains_owner ON domains (owner);
+
+CREATE TABLE registrations (
+    domain TEXT PRIMARY KEY,
+    origin TEXT NOT NULL,
+    expire INTEGER,
+    capacity INTEGER NOT NULL,
+    children INTEGER NOT NULL
+);
+CREATE INDEX registrations_origin ON registrations (origin);
+
+CREATE TABLE subdomain_events (
+    id INTEGER PRIMARY KEY,
+    parent TEXT NOT NULL,
+    subdomain TEXT NOT NULL,
+    name TEXT NOT NULL,
+    to_account TEXT NOT NULL
+);
+CREATE INDEX subdomain_events_parent ON subdomain_events (parent);
+CREATE INDEX subdomain_events_subdomain ON subdomain_events (subdomain);
+CREATE INDEX subdomain_events_to_account ON subdomain_events (to_account);
+
+CREATE TABLE transfers (
+    id TEXT PRIMARY KEY,
+    domain TEXT NOT NULL,
+    from_account TEXT,
+    to_account TEXT NOT NULL,
+    block_number INTEGER NOT NULL,
+    triggered_date INTEGER NOT NULL,
+    transaction_id TEXT NOT NULL
+);
+CREATE INDEX transfers_domain ON transfers (domain);
+CREATE INDEX transfers_from_account ON transfers (from_account);
+CREATE INDEX transfers_to_account ON transfers (to_account);
+
+CREATE TABLE resolvers (
+    domain TEXT PRIMARY KEY,
+    resolver TEXT NOT NULL,
+    addr TEXT,
+    content_hash TEXT,
+    content_uri TEXT,
+    -- JSON array of coin types
+    coin_types TEXT NOT NULL,
+    -- JSON array of the text keys the resolver lists
+    keys TEXT NOT NULL
+);
+CREATE INDEX resolvers_addr ON resolvers (addr);
+
+CREATE TABLE resolver_records (
+    domain TEXT NOT NULL,
+    -- text or link
+    kind TEXT NOT NULL,
+    key_hash TEXT NOT NULL,
+    key TEXT,
+    value TEXT NOT NULL,
+    block_number INTEGER NOT NULL,
+    PRIMARY KEY (domain, kind, key_hash)
+);
+"#;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+    pub meta: Meta,
+    pub domains: Vec<DomainRecord>,
+    pub registrations: Records,
+    pub subdomain_events: Vec<NewSubdomain>,
+    pub transfers: Vec<TransferRecord>,
+    pub resolvers: Vec<ResolverRecord>,
+}
+
+impl Snapshot {
+    /// Fetch every dataset at `meta.block_number`.
+    pub async fn query(meta: Meta) -> anyhow::Result<Self> {
+        let block = meta.block_number;
+
+        Ok(Self {
+            domains: DomainsBuilder::query(block).await?,
+            registrations: Records(
+                query_all_at::<RecordsBuilder>(block)
+                    .await
+                    .into_iter()
+                    .collect::<BTreeMap<_, _>>(),
+            ),
+            subdomain_events: query_all_at::<NewSubdomainQueryBuilder>(block).await,
+            transfers: TransfersBuilder::query(&Default::default(), block).await,
+            resolvers: resolvers::query_resolver_records(block).await,
+            meta,
+        })
+    }
+
+    /// Bring `previous` to `meta.block_number`, fetching only the domains,
+    /// registrations and resolvers written after its block and the events
+    /// emitted since.
+    ///
+    /// Entities removed from the subgraph stay in the result, take a full
+    /// snapshot now and then to drop them.
+    pub async fn query_since(previous: Snapshot, meta: Meta) -> anyhow::Result<Self> {
+        if previous.meta.deployment != meta.deployment {
+            anyhow::bail!(
+                "previous snapshot is of deployment {}, the subgraph now serves {}",
+                previous.meta.deployment,
+                meta.deployment
+            );
+        }
+        let after = previous.meta.block_number;
+        let block = meta.block_number;
+        if after > block {
+            anyhow::bail!("previous snapshot is at block {after}, the subgraph only at {block}");
+        }
+
+        let changed_domains = DomainsBuilder::query_changed(after, block).await?;
+        let changed_registrations = RecordsBuilder::query_changed(after, block).await;
+        let subdomain_events = NewSubdomainQueryBuilder::query_after(after, block).await;
+        let transfers = TransfersBuilder::query(
+            &TransferFilter {
+                after_block: Some(after),
+                ..Default::default()
+            },
+            block,
+        )
+        .await;
+
+        let mut domains = previous
+            .domains
+            .into_iter()
+            .chain(changed_domains)
+            .map(|d| (d.id.clone(), d))
+            .collect::<BTreeMap<_, _>>();
+        let mut registrations = previous.registrations.0;
+
+        // a renewal writes the registration but not its domain, a new
+        // subdomain the domain but not its registration
+        for (id, r) in changed_registrations {
+            if let Some(d) = domains.get_mut(&id) {
+                d.expire = r.expire;
+                d.capacity = Some(r.capacity);
+            }
+            registrations.insert(id, r);
+        }
+        for d in domains.values() {
+            if let Some(r) = registrations.get_mut(&d.id) {
+                r.children = d.subdomain_count;
+            }
+        }
+
+        Ok(Self {
+            domains: domains.into_values().collect(),
+            registrations: Records(registrations),
+            subdomain_events: previous
+                .subdomain_events
+                .into_iter()
+                .chain(subdomain_events)
+                .collect(),
+            transfers: previous.transfers.into_iter().chain(transfers).collect(),
+            resolvers: resolvers::query_resolver_records_since(previous.resolvers, after, block)
+                .await,
+            meta,
+        })
+    }
+
+    /// Load a snapshot written as JSON or SQLite, compressed or not.
+    /// Refuses snapshots of another [`FORMAT_VERSION`].
+    pub async fn load(path: &str) -> anyhow::Result<Self> {
+        let bytes = output::read_file(path).await?;
+        if !bytes.starts_with(SQLITE_MAGIC) {
+            return Ok(envelope::open::<Self>(&bytes, "snapshot")?.data);
+        }
+
+        // SQLite only opens files, decompress to one first
+        let tmp = std::env::temp_dir().join(format!("snapshot-{}.sqlite", std::process::id()));
+        tokio::fs::write(&tmp, &bytes).await?;
+        let res = Self::read_sqlite(&tmp.to_string_lossy());
+        tokio::fs::remove_file(&tmp).await?;
+        res
+    }
+
+    /// Read back a database written by [`Snapshot::write_sqlite`].
+    pub fn read_sqlite(path: &str) -> anyhow::Result<Self> {
+        let conn = Connection::open(path)?;
+
+        let (format_version, dataset, meta) = conn.query_row(
+            "SELECT format_version, dataset, deployment, block_number, block_hash, block_timestamp
+             FROM metadata",
+            [],
+            |row| {
+                Ok((
+                    row.get::<_, u32>(0)?,
+                    row.get::<_, String>(1)?,
+                    Meta {
+                        deployment: row.get(2)?,
+                        block_number: row.get(3)?,
+                        block_hash: row.get(4)?,
+                        block_timestamp: row.get(5)?,
+                        has_indexing_errors: false,
+                    },
+                ))
+            },
+        )?;
+        if format_version != FORMAT_VERSION {
+            anyhow::bail!(
+                "`snapshot` has format version {format_version}, expected {FORMAT_VERSION}"
+            );
+        }
+        if dataset != "snapshot" {
+            anyhow::bail!("expected `snapshot`, found `{dataset}`");
+        }
+
+        let domains = conn
+            .prepare("SELECT * FROM domains ORDER BY rowid")?
+            .query_map([], |row| {
+                Ok(DomainRecord {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    label_name: row.get(2)?,
+                    labelhash: row.get(3)?,
+                    parent: row.get(4)?,
+                    owner: row.get(5)?,
+                    resolved_address: row.get(6)?,
+                    resolver: row.get(7)?,
+                    subdomain_count: row.get(8)?,
+                    created_at: row.get(9)?,
+                    expire: row.get(10)?,
+                    capacity: row.get(11)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+
+        let registrations = conn
+            .prepare("SELECT * FROM registrations")?
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    Record {
+                        origin: row.get(1)?,
+                        expire: row.get(2)?,
+                        capacity: row.get(3)?,
+                        children: row.get(4)?,
+                    },
+                ))
+            })?
+            .collect::<Result<_, _>>()?;
+
+        let subdomain_events = conn
+            .prepare(
+                "SELECT parent, subdomain, name, to_account FROM subdomain_events ORDER BY id",
+            )?
+            .query_map([], |row| {
+                Ok(NewSubdomain {
+                    token_id: row.get(0)?,
+                    subtoken_id: row.get(1)?,
+                    name: row.get(2)?,
+                    to: row.get(3)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+
+        // the transfer's name is the one of its domain
+        let transfers = conn
+            .prepare(
+                "SELECT t.id, t.domain, d.name, t.from_account, t.to_account, t.block_number,
+                    t.triggered_date, t.transaction_id
+                 FROM transfers t LEFT JOIN domains d ON d.id = t.domain
+                 ORDER BY t.rowid",
+            )?
+            .query_map([], |row| {
+                Ok(TransferRecord {
+                    id: row.get(0)?,
+                    domain: row.get(1)?,
+                    name: row.get(2)?,
+                    from: row.get(3)?,
+                    to: row.get(4)?,
+                    block_number: row.get(5)?,
+                    triggered_date: row.get(6)?,
+                    transaction_id: row.get(7)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+
+        let mut records = BTreeMap::<_, (Vec<KeyRecord>, Vec<KeyRecord>)>::new();
+        let mut select = conn.prepare(
+            "SELECT domain, kind, key_hash, key, value, block_number FROM resolver_records
+             ORDER BY domain, kind, key_hash",
+        )?;
+        let mut rows = select.query([])?;
+        while let Some(row) = rows.next()? {
+            let (texts, links) = records.entry(row.get::<_, String>(0)?).or_default();
+            let list = match row.get_ref(1)?.as_str()? {
+                "text" => texts,
+                "link" => links,
+                kind => anyhow::bail!("unknown resolver record kind `{kind}`"),
+            };
+            list.push(KeyRecord {
+                key_hash: row.get(2)?,
+                key: row.get(3)?,
+                value: row.get(4)?,
+                block_number: row.get(5)?,
+            });
+        }
+
+        // the resolver's name is the one of its domain
+        let mut select = conn.prepare(
+            "SELECT r.domain, d.name, r.resolver, r.addr, r.content_hash, r.content_uri,
+                r.coin_types, r.keys
+             FROM resolvers r LEFT JOIN domains d ON d.id = r.domain
+             ORDER BY r.rowid",
+        )?;
+        let mut rows = select.query([])?;
+        let mut resolvers = Vec::new();
+        while let Some(row) = rows.next()? {
+            let domain = row.get::<_, String>(0)?;
+            let (texts, links) = records.remove(&domain).unwrap_or_default();
+            resolvers.push(ResolverRecord {
+                name: row.get(1)?,
+                resolver: row.get(2)?,
+                addr: row.get(3)?,
+                content_hash: row.get(4)?,
+                content_uri: row.get(5)?,
+                coin_types: serde_json::from_str(&row.get::<_, String>(6)?)?,
+                keys: serde_json::from_str(&row.get::<_, String>(7)?)?,
+                domain,
+                texts,
+                links,
+            });
+        }
+
+        Ok(Self {
+            meta,
+            domains,
+            registrations: Records(registrations),
+            subdomain_events,
+            transfers,
+            resolvers,
+        })
+    }
+
+    /// Rows across every dataset.
+    pub fn len(&self) -> usize {
+        self.domains.len()
+            + self.registrations.0.len()
+            + self.subdomain_events.len()
+            + self.transfers.len()
+            + self.resolvers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn header(&self) -> Header {
+        Header::new("snapshot", &self.meta).with_record_count(self.len())
+    }
+
+    /// SHA-256 of the datasets serialized as JSON, see [`output::checksum`].
+    pub fn checksum(&self) -> anyhow::Result<String> {
+        let content = serde_json::to_vec(&(
+            &self.domains,
+            &self.registrations,
+            &self.subdomain_events,
+            &self.transfers,
+            &self.resolvers,
+        ))?;
+        Ok(output::checksum(&content))
+    }
+
+    /// Every address mentioned as an owner, resolved address, transfer
+    /// party or subdomain receiver.
+    pub fn accounts(&self) -> BTreeSet<&str> {
+        let domains = self.domains.iter().flat_map(|d| {
+            [d.owner.as_deref(), d.resolved_address.as_deref()]
+                .into_iter()
+                .flatten()
+        });
+        let transfers = self.transfers.iter().flat_map(|t| {
+            [t.from.as_deref(), Some(t.to.as_str())]
+                .into_iter()
+                .flatten()
+        });
+        let subdomains = self.subdomain_events.iter().map(|e| e.to.as_str());
+
+        domains.chain(transfers).chain(subdomains).collect()
+    }
+
+    /// Create the database at `path`, which must not exist yet. `header` is
+    /// usually [`Snapshot::header`] with the export's params.
+    pub fn write_sqlite(&self, header: &Header, path: &str) -> anyhow::Result<()> {
+        let mut conn = Connection::open(path)?;
+        let tx = conn.transaction()?;
+        tx.execute_batch(SCHEMA)?;
+
+        tx.execute(
+            "INSERT INTO metadata VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                header.format_version,
+                header.dataset,
+                header.endpoint,
+                header.deployment,
+                header.block_number,
+                header.block_hash,
+                header.block_timestamp,
+                header.params.to_string(),
+                header.record_count,
+                header.tool_version,
+                time::OffsetDateTime::now_utc().unix_timestamp(),
+                self.checksum()?,
+            ],
+        )?;
+
+        {
+            let mut insert = tx.prepare("INSERT INTO accounts VALUES (?1)")?;
+            for account in self.accounts() {
+                insert.execute(params![account])?;
+            }
+
+            let mut insert = tx.prepare(
+                "INSERT INTO domains VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            )?;
+            for d in self.domains.iter() {
+                insert.execute(params![
+                    d.id,
+                    d.name,
+                    d.label_name,
+                    d.labelhash,
+                    d.parent,
+                    d.owner,
+                    d.resolved_address,
+                    d.resolver,
+                    d.subdomain_count,
+                    d.created_at,
+                    d.expire,
+                    d.capacity,
+                ])?;
+            }
+
+            let mut insert = tx.prepare("INSERT INTO registrations VALUES (?1, ?2, ?3, ?4, ?5)")?;
+            for (domain, r) in self.registrations.0.iter() {
+                insert.execute(params![domain, r.origin, r.expire, r.capacity, r.children])?;
+            }
+
+            let mut insert = tx.prepare(
+                "INSERT INTO subdomain_events (parent, subdomain, name, to_account)
+                 VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for e in self.subdomain_events.iter() {
+                insert.execute(params![e.token_id, e.subtoken_id, e.name, e.to])?;
+            }
+
+            let mut insert =
+                tx.prepare("INSERT INTO transfers VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)")?;
+            for t in self.transfers.iter() {
+                insert.execute(params![
+                    t.id,
+                    t.domain,
+                    t.from,
+                    t.to,
+                    t.block_number,
+                    t.triggered_date,
+                    t.transaction_id,
+                ])?;
+            }
+
+            let mut insert =
+                tx.prepare("INSERT INTO resolvers VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)")?;
+            let mut insert_record =
+                tx.prepare("INSERT INTO resolver_records VALUES (?1, ?2, ?3, ?4, ?5, ?6)")?;
+            for r in self.resolvers.iter() {
+                insert.execute(params![
+                    r.domain,
+                    r.resolver,
+                    r.addr,
+                    r.content_hash,
+                    r.content_uri,
+                    serde_json::to_string(&r.coin_types)?,
+                    serde_json::to_string(&r.keys)?,
+                ])?;
+
+                let texts = r.texts.iter().map(|k| ("text", k));
+                let links = r.links.iter().map(|k| ("link", k));
+                for (kind, k) in texts.chain(links) {
+                    insert_record.execute(params![
+                        r.domain,
+                        kind,
+                        k.key_hash,
+                        k.key,
+                        k.value,
+                        k.block_number,
+                    ])?;
+                }
+            }
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+}
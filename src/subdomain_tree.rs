@@ -0,0 +1,320 @@
+//! Domain hierarchy rebuilt from `Domain.parent` and `NewSubdomain` events.
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+
+use crate::{domains::DomainRecord, new_subdomains::NewSubdomain};
+
+#[derive(Debug, Default)]
+struct Node {
+    /// Listed as a domain or created by a `NewSubdomain` event, rather than
+    /// only named as a parent.
+    known: bool,
+    name: Option<String>,
+    subdomain_count: Option<i32>,
+    parent: Option<String>,
+    children: BTreeSet<String>,
+}
+
+/// A domain with its subtree, for nested export.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeNode {
+    pub id: String,
+    pub name: Option<String>,
+    pub depth: usize,
+    pub descendants_num: usize,
+    pub children: Vec<TreeNode>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Edge {
+    pub parent: String,
+    pub child: String,
+    pub name: Option<String>,
+    /// Depth of `child`, roots are 0.
+    pub depth: usize,
+}
+
+/// A domain whose parent is neither a listed domain nor a subdomain from an
+/// event. It is exported under the parent's id all the same.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Orphan {
+    pub id: String,
+    pub name: Option<String>,
+    pub parent: String,
+}
+
+/// A domain whose `subdomainCount` differs from the children found for it.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CountMismatch {
+    pub id: String,
+    pub name: Option<String>,
+    pub subdomain_count: i32,
+    pub children_num: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct SubdomainTree {
+    nodes: BTreeMap<String, Node>,
+}
+
+impl SubdomainTree {
+    pub fn build(domains: &[DomainRecord], events: &[NewSubdomain]) -> Self {
+        let mut tree = SubdomainTree::default();
+
+        for domain in domains {
+            let node = tree.nodes.entry(domain.id.clone()).or_default();
+            node.known = true;
+            node.name = domain.name.clone();
+            node.subdomain_count = Some(domain.subdomain_count);
+            if let Some(parent) = &domain.parent {
+                tree.link(parent, &domain.id);
+            }
+        }
+
+        // events cover children the domain list does not link, such as ones
+        // whose parent field was never set
+        for event in events {
+            let child = tree.nodes.entry(event.subtoken_id.clone()).or_default();
+            child.known = true;
+            if child.name.is_none() {
+                child.name = Some(event.name.clone());
+            }
+            if child.parent.is_none() {
+                tree.link(&event.token_id, &event.subtoken_id);
+            }
+        }
+
+        tree
+    }
+
+    fn link(&mut self, parent: &str, child: &str) {
+        self.nodes.entry(child.into()).or_default().parent = Some(parent.into());
+        self.nodes
+            .entry(parent.into())
+            .or_default()
+            .children
+            .insert(child.into());
+    }
+
+    pub fn roots(&self) -> impl Iterator<Item = &String> {
+        self.nodes
+            .iter()
+            .filter(|(_, node)| node.parent.is_none())
+            .map(|(id, _)| id)
+    }
+
+    /// Every root with its subtree. Domains on a parent cycle have no root
+    /// and are left out, see [`SubdomainTree::cycles`].
+    pub fn nested(&self) -> Vec<TreeNode> {
+        self.roots().map(|root| self.subtree(root)).collect()
+    }
+
+    /// Built with an explicit stack, so deep hierarchies can't overflow it.
+    fn subtree(&self, root: &str) -> TreeNode {
+        // nodes whose children are still being built, with those built so far
+        let mut stack = vec![(
+            root,
+            self.nodes[root].children.iter(),
+            Vec::<TreeNode>::new(),
+        )];
+
+        loop {
+            let (_, children, _) = stack.last_mut().expect("the root is popped last");
+            if let Some(child) = children.next() {
+                stack.push((child, self.nodes[child].children.iter(), Vec::new()));
+                continue;
+            }
+
+            let (id, _, children) = stack.pop().expect("the root is popped last");
+            let node = TreeNode {
+                id: id.into(),
+                name: self.nodes[id].name.clone(),
+                depth: stack.len(),
+                descendants_num: children.iter().map(|c| c.descendants_num + 1).sum(),
+                children,
+            };
+            match stack.last_mut() {
+                Some((_, _, siblings)) => siblings.push(node),
+                None => return node,
+            }
+        }
+    }
+
+    /// Every parent/child link, breadth first from the roots.
+    pub fn edges(&self) -> Vec<Edge> {
+        let mut res = Vec::new();
+        let mut level = self.roots().cloned().collect::<Vec<_>>();
+        let mut depth = 0;
+
+        while !level.is_empty() {
+            depth += 1;
+            let mut next = Vec::new();
+            for parent in level {
+                for child in self.nodes[&parent].children.iter() {
+                    res.push(Edge {
+                        parent: parent.clone(),
+                        child: child.clone(),
+                        name: self.nodes[child].name.clone(),
+                        depth,
+                    });
+                    next.push(child.clone());
+                }
+            }
+            level = next;
+        }
+
+        res
+    }
+
+    /// Domains whose parent is only known from the domains pointing at it.
+    pub fn orphans(&self) -> Vec<Orphan> {
+        self.nodes
+            .iter()
+            .filter(|(_, node)| node.known)
+            .filter_map(|(id, node)| {
+                let parent = node.parent.as_ref()?;
+                (!self.nodes[parent].known).then(|| Orphan {
+                    id: id.clone(),
+                    name: node.name.clone(),
+                    parent: parent.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Domains that are their own ancestor, one list per cycle in parent
+    /// order. Neither they nor their subtrees are reachable from a root.
+    pub fn cycles(&self) -> Vec<Vec<String>> {
+        let mut reached = BTreeSet::new();
+        let mut stack = self.roots().collect::<Vec<_>>();
+        while let Some(id) = stack.pop() {
+            reached.insert(id);
+            stack.extend(self.nodes[id].children.iter());
+        }
+
+        let mut res = Vec::new();
+        for start in self.nodes.keys() {
+            if reached.contains(start) {
+                continue;
+            }
+            // follow the parents until one repeats, the walk before it
+            // leads into the cycle
+            let mut path = Vec::new();
+            let mut id = start;
+            while !reached.contains(id) && !path.contains(&id) {
+                path.push(id);
+                id = self.nodes[id]
+                    .parent
+                    .as_ref()
+                    .expect("unreached nodes have a parent");
+            }
+            if let Some(at) = path.iter().position(|p| *p == id) {
+                res.push(path[at..].iter().map(|id| id.to_string()).collect());
+            }
+            reached.extend(path);
+        }
+
+        res
+    }
+
+    pub fn count_mismatches(&self) -> Vec<CountMismatch> {
+        self.nodes
+            .iter()
+            .filter_map(|(id, node)| {
+                let subdomain_count = node.subdomain_count?;
+                (subdomain_count as usize != node.children.len()).then(|| CountMismatch {
+                    id: id.clone(),
+                    name: node.name.clone(),
+                    subdomain_count,
+                    children_num: node.children.len(),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn domain(id: usize, parent: Option<usize>) -> DomainRecord {
+        DomainRecord {
+            id: format!("d{id}"),
+            name: None,
+            label_name: None,
+            labelhash: None,
+            parent: parent.map(|p| format!("d{p}")),
+            owner: None,
+            resolved_address: None,
+            resolver: None,
+            subdomain_count: 0,
+            created_at: 0,
+            expire: None,
+            capacity: None,
+        }
+    }
+
+    #[test]
+    fn deep_chains_do_not_overflow_the_stack() {
+        let domains = (0..100_000)
+            .map(|i| domain(i, i.checked_sub(1)))
+            .collect::<Vec<_>>();
+        let tree = SubdomainTree::build(&domains, &[]);
+
+        let roots = tree.nested();
+        assert_eq!(roots.len(), 1);
+        assert_eq!(roots[0].descendants_num, 99_999);
+
+        // taken apart one level at a time, dropping the chain whole would
+        // recurse as deep as it is
+        let (mut stack, mut deepest) = (roots, None);
+        while let Some(mut node) = stack.pop() {
+            stack.append(&mut node.children);
+            deepest = Some((node.id, node.depth));
+        }
+        assert_eq!(deepest, Some(("d99999".to_string(), 99_999)));
+    }
+
+    #[test]
+    fn orphans_and_cycles_are_reported() {
+        let domains = [
+            domain(0, None),
+            domain(1, Some(0)),
+            // parent 9 is not a domain
+            domain(2, Some(9)),
+            domain(3, Some(4)),
+            domain(4, Some(5)),
+            domain(5, Some(3)),
+            // hangs off the cycle
+            domain(6, Some(4)),
+        ];
+        let events = [NewSubdomain {
+            to: String::new(),
+            token_id: "d1".to_string(),
+            subtoken_id: "d7".to_string(),
+            name: "seven".to_string(),
+        }];
+        let tree = SubdomainTree::build(&domains, &events);
+
+        let orphans = tree.orphans();
+        assert_eq!(orphans.len(), 1);
+        assert_eq!(
+            (orphans[0].id.as_str(), orphans[0].parent.as_str()),
+            ("d2", "d9")
+        );
+        assert_eq!(tree.cycles(), vec![vec!["d3", "d4", "d5"]]);
+
+        let roots = tree.nested();
+        assert_eq!(
+            roots.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(),
+            ["d0", "d9"]
+        );
+        assert_eq!(roots[0].descendants_num, 2);
+        assert_eq!(tree.edges().len(), 3);
+    }
+}
@@ -1,7 +1,7 @@
 /*!
 ```
-query QueryDomains($skip: Int = 10) {
-  domains(skip: $skip, first: 1000) {
+query QueryDomains($skip: Int = 10, $block: Block_height) {
+  domains(skip: $skip, first: 1000, block: $block) {
     id
   }
 }
@@ -11,35 +11,28 @@ query QueryDomains($skip: Int = 10) {
 // generate by https://generator.cynic-rs.dev/
 #[cynic::schema_for_derives(file = r#"schema.gql"#, module = "schema")]
 mod queries {
-    use super::schema;
+    use crate::{
+        fragments::{BlockHeight, DomainId},
+        schema,
+    };
 
     #[derive(cynic::QueryVariables, Debug)]
     pub struct QueryDomainsVariables {
         pub skip: Option<i32>,
+        pub block: Option<BlockHeight>,
     }
 
     #[derive(cynic::QueryFragment, Debug)]
     #[cynic(graphql_type = "Query", variables = "QueryDomainsVariables")]
     pub struct QueryDomains {
-        #[arguments(skip: $skip, first: 1000)]
-        pub domains: Vec<Domain>,
+        #[arguments(skip: $skip, first: 1000, block: $block)]
+        pub domains: Vec<DomainId>,
     }
-
-    #[derive(cynic::QueryFragment, Debug)]
-    pub struct Domain {
-        pub id: Bytes,
-    }
-
-    #[derive(cynic::Scalar, Debug, Clone)]
-    pub struct Bytes(pub String);
 }
 
-#[allow(non_snake_case, non_camel_case_types)]
-mod schema {
-    cynic::use_schema!(r#"schema.gql"#);
-}
+use serde::Serialize;
 
-use crate::{BuildQuery, IsFull, DOMAIN_ID_LEN};
+use crate::{fragments::BlockHeight, output::Tabular, BuildQuery, IsFull, DOMAIN_ID_LEN};
 
 use self::queries::{QueryDomains, QueryDomainsVariables};
 
@@ -50,9 +43,10 @@ impl BuildQuery for QueryTokenList {
 
     type ResponseData = QueryDomains;
 
-    fn build_query(offset: i32) -> cynic::Operation<Self::ResponseData, Self::Vars> {
+    fn build_query(offset: i32, block: i32) -> cynic::Operation<Self::ResponseData, Self::Vars> {
         <queries::QueryDomains as cynic::QueryBuilder>::build(queries::QueryDomainsVariables {
             skip: Some(offset),
+            block: Some(BlockHeight::number(block)),
         })
     }
 }
@@ -69,3 +63,21 @@ impl IsFull for QueryDomains {
             .map(|domain| crate::HandleId::handle_id::<DOMAIN_ID_LEN>(&domain.id.0))
     }
 }
+
+#[derive(Serialize)]
+pub struct TokenRow<'a> {
+    pub id: &'a str,
+}
+
+/// Every domain id, as [`QueryTokenList`] returns them.
+#[derive(Debug, Serialize)]
+pub struct TokenList(pub Vec<String>);
+
+/// One id per row.
+impl Tabular for TokenList {
+    type Row<'a> = TokenRow<'a>;
+
+    fn rows(&self) -> impl Iterator<Item = Self::Row<'_>> {
+        self.0.iter().map(|id| TokenRow { id })
+    }
+}
@@ -0,0 +1,279 @@
+/*!
+```
+query QueryTransfers($skip: Int = 10, $block: Block_height, $filter: Transfer_filter) {
+  transfers(
+    skip: $skip
+    first: 1000
+    block: $block
+    where: $filter
+    orderBy: blockNumber
+    orderDirection: asc
+  ) {
+    id
+    blockNumber
+    triggeredDate
+    transactionID
+    from {
+      id
+    }
+    to {
+      id
+    }
+    domain {
+      id
+      name
+      createdAt
+    }
+  }
+}
+```
+*/
+#[cynic::schema_for_derives(file = r#"schema.gql"#, module = "schema")]
+mod queries {
+    use crate::{
+        fragments::{AccountFields, BigInt, BlockHeight, Bytes, DomainFields, DomainFilter},
+        schema,
+    };
+
+    #[derive(cynic::QueryVariables, Debug)]
+    pub struct QueryTransfersVariables {
+        pub skip: Option<i32>,
+        pub block: Option<BlockHeight>,
+        pub filter: Option<TransferFilter>,
+    }
+
+    #[derive(cynic::InputObject, Debug, Default, Clone)]
+    #[cynic(graphql_type = "Transfer_filter")]
+    pub struct TransferFilter {
+        #[cynic(skip_serializing_if = "Option::is_none")]
+        pub domain: Option<String>,
+        #[cynic(rename = "domain_in", skip_serializing_if = "Option::is_none")]
+        pub domain_in: Option<Vec<String>>,
+        #[cynic(rename = "domain_", skip_serializing_if = "Option::is_none")]
+        pub domain_: Option<DomainFilter>,
+        #[cynic(skip_serializing_if = "Option::is_none")]
+        pub from: Option<String>,
+        #[cynic(skip_serializing_if = "Option::is_none")]
+        pub to: Option<String>,
+        #[cynic(rename = "triggeredDate_gte", skip_serializing_if = "Option::is_none")]
+        pub triggered_date_gte: Option<BigInt>,
+        #[cynic(rename = "triggeredDate_lt", skip_serializing_if = "Option::is_none")]
+        pub triggered_date_lt: Option<BigInt>,
+        #[cynic(rename = "blockNumber_gt", skip_serializing_if = "Option::is_none")]
+        pub block_number_gt: Option<i32>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    #[cynic(graphql_type = "Query", variables = "QueryTransfersVariables")]
+    pub struct QueryTransfers {
+        #[arguments(skip: $skip, first: 1000, block: $block, where: $filter, orderBy: blockNumber, orderDirection: asc)]
+        pub transfers: Vec<Transfer>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug)]
+    pub struct Transfer {
+        pub id: cynic::Id,
+        pub block_number: i32,
+        pub triggered_date: BigInt,
+        #[cynic(rename = "transactionID")]
+        pub transaction_id: Bytes,
+        pub from: Option<AccountFields>,
+        pub to: AccountFields,
+        pub domain: DomainFields,
+    }
+}
+
+use std::collections::{BTreeMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    fragments::{BigInt, BlockHeight, DomainFilter},
+    query_pages, query_pages_each, HandleId, IsFull, ACCOUNT_ID_LEN, DOMAIN_ID_LEN,
+};
+
+/// One ownership change. `from` is `None` for the mint.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferRecord {
+    pub id: String,
+    pub domain: String,
+    pub name: Option<String>,
+    pub from: Option<String>,
+    pub to: String,
+    pub block_number: i32,
+    pub triggered_date: i64,
+    pub transaction_id: String,
+}
+
+/// Restricts which transfers are fetched. Every field is optional and they
+/// combine with AND.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct TransferFilter {
+    /// Domain id (`0x...`) or full name.
+    pub domain: Option<String>,
+    /// Matches transfers from or to this address.
+    pub account: Option<String>,
+    /// Inclusive lower bound on `triggeredDate`, unix seconds.
+    pub since: Option<i64>,
+    /// Exclusive upper bound on `triggeredDate`, unix seconds.
+    pub until: Option<i64>,
+    /// Only transfers in blocks after this one.
+    pub after_block: Option<i32>,
+}
+
+impl TransferFilter {
+    /// The server side filters to run. The account match needs one for each
+    /// side of the transfer since the subgraph has no `or` filter.
+    fn to_queries(&self) -> Vec<queries::TransferFilter> {
+        let mut base = queries::TransferFilter {
+            triggered_date_gte: self.since.map(|t| BigInt(t.to_string())),
+            triggered_date_lt: self.until.map(|t| BigInt(t.to_string())),
+            block_number_gt: self.after_block,
+            ..Default::default()
+        };
+
+        match &self.domain {
+            Some(domain) if domain.starts_with("0x") => {
+                base.domain = Some(domain.to_lowercase());
+            }
+            Some(name) => {
+                base.domain_ = Some(DomainFilter {
+                    name: Some(name.clone()),
+                    ..Default::default()
+                });
+            }
+            None => {}
+        }
+
+        match &self.account {
+            Some(account) => {
+                let account = account.to_lowercase();
+                vec![
+                    queries::TransferFilter {
+                        from: Some(account.clone()),
+                        ..base.clone()
+                    },
+                    queries::TransferFilter {
+                        to: Some(account),
+                        ..base
+                    },
+                ]
+            }
+            None => vec![base],
+        }
+    }
+}
+
+pub struct TransfersBuilder;
+
+/// Domain ids per `domain_in` filter of [`TransfersBuilder::query_domains`].
+const DOMAINS_PER_QUERY: usize = 500;
+
+impl TransfersBuilder {
+    fn build_query(
+        offset: i32,
+        block: i32,
+        filter: queries::TransferFilter,
+    ) -> cynic::Operation<queries::QueryTransfers, queries::QueryTransfersVariables> {
+        <queries::QueryTransfers as cynic::QueryBuilder>::build(queries::QueryTransfersVariables {
+            skip: Some(offset),
+            block: Some(BlockHeight::number(block)),
+            filter: Some(filter),
+        })
+    }
+
+    /// Every transfer matching `filter` at `block`, ordered by block.
+    pub async fn query(filter: &TransferFilter, block: i32) -> Vec<TransferRecord> {
+        let mut res = BTreeMap::new();
+
+        for filter in filter.to_queries() {
+            let transfers =
+                query_pages(|offset| Self::build_query(offset, block, filter.clone())).await;
+            // a self transfer matches both sides of an account filter
+            res.extend(transfers.into_iter().map(|t| (t.id.clone(), t)));
+        }
+
+        let mut res = res.into_values().collect::<Vec<_>>();
+        res.sort_by_key(|t| t.block_number);
+        res
+    }
+
+    /// Every transfer of the domains in `domains`, at `block`, ordered by
+    /// block. The ids go to the subgraph in chunks to keep requests small.
+    pub async fn query_domains(domains: &[String], block: i32) -> Vec<TransferRecord> {
+        let mut res = Vec::new();
+        for chunk in domains.chunks(DOMAINS_PER_QUERY) {
+            let filter = queries::TransferFilter {
+                domain_in: Some(chunk.iter().map(|d| d.to_lowercase()).collect()),
+                ..Default::default()
+            };
+            res.extend(
+                query_pages(|offset| Self::build_query(offset, block, filter.clone())).await,
+            );
+        }
+
+        res.sort_by_key(|t| t.block_number);
+        res
+    }
+
+    /// Like [`TransfersBuilder::query`] but hands every transfer to
+    /// `on_transfer` as its page arrives. Transfers are in block order within
+    /// each server side query, an account filter runs two in turn.
+    pub async fn query_each(
+        filter: &TransferFilter,
+        block: i32,
+        mut on_transfer: impl FnMut(TransferRecord) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let mut seen = HashSet::new();
+
+        for filter in filter.to_queries() {
+            query_pages_each(
+                |offset| Self::build_query(offset, block, filter.clone()),
+                |transfer| {
+                    if !seen.insert(transfer.id.clone()) {
+                        return Ok(());
+                    }
+                    on_transfer(transfer)
+                },
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Group transfers into one ownership timeline per domain.
+pub fn timelines(transfers: Vec<TransferRecord>) -> BTreeMap<String, Vec<TransferRecord>> {
+    let mut res = BTreeMap::<_, Vec<_>>::new();
+    for transfer in transfers {
+        res.entry(transfer.domain.clone())
+            .or_default()
+            .push(transfer);
+    }
+    res
+}
+
+impl IsFull for queries::QueryTransfers {
+    type Item = TransferRecord;
+
+    fn len(&self) -> usize {
+        self.transfers.len()
+    }
+
+    fn into_iter(self) -> impl IntoIterator<Item = Self::Item> {
+        IntoIterator::into_iter(self.transfers).map(|transfer| TransferRecord {
+            id: transfer.id.inner().to_string(),
+            domain: transfer.domain.id.0.handle_id::<DOMAIN_ID_LEN>(),
+            name: transfer.domain.name,
+            from: transfer
+                .from
+                .map(|from| from.id.0.handle_id::<ACCOUNT_ID_LEN>()),
+            to: transfer.to.id.0.handle_id::<ACCOUNT_ID_LEN>(),
+            block_number: transfer.block_number,
+            triggered_date: transfer.triggered_date.0.parse().unwrap(),
+            transaction_id: transfer.transaction_id.0,
+        })
+    }
+}
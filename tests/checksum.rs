@@ -0,0 +1,168 @@
+//! The same records give the same bytes and the same checksum in every
+//! format, whatever order the subgraph's pages brought them in.
+use query::{
+    accouts::AllAccounts,
+    columnar,
+    envelope::{self, Header},
+    meta::Meta,
+    output::{self, Compression, Format, Tabular},
+};
+
+fn header() -> Header {
+    let meta = Meta {
+        deployment: "QmTest".to_string(),
+        block_number: 1_000,
+        block_hash: Some(format!("0x{}", "ab".repeat(32))),
+        block_timestamp: Some(1_667_908_800),
+        has_indexing_errors: false,
+    };
+    Header::new("all_accounts", &meta).with_record_count(3)
+}
+
+/// Three accounts, inserted in reverse order, names included, when
+/// `reversed`.
+fn accounts(reversed: bool) -> AllAccounts {
+    let mut accounts = vec![
+        serde_json::json!({
+            "id": format!("0x{}", "01".repeat(20)),
+            "domainsNum": 2,
+            "domains": ["alice.dot", "bob.dot"],
+        }),
+        serde_json::json!({
+            "id": format!("0x{}", "02".repeat(20)),
+            "domainsNum": 1,
+            "domains": ["carol.dot"],
+        }),
+        serde_json::json!({
+            "id": format!("0x{}", "03".repeat(20)),
+            "domainsNum": 3,
+            "domains": ["dave.dot", "erin.dot", "frank.dot"],
+        }),
+    ];
+    if reversed {
+        accounts.reverse();
+        for account in accounts.iter_mut() {
+            account["domains"]
+                .as_array_mut()
+                .expect("domains is a list")
+                .reverse();
+        }
+    }
+
+    serde_json::from_value(serde_json::json!({
+        "accountsNum": accounts.len(),
+        "accounts": accounts,
+    }))
+    .expect("fixture is a valid dataset")
+}
+
+#[test]
+fn insertion_order_does_not_change_bytes_or_checksums() {
+    let header = header();
+    let (a, b) = (accounts(false), accounts(true));
+
+    for format in [Format::Json, Format::Csv, Format::Ndjson] {
+        let bytes_a = output::encode(&header, &a, format).unwrap();
+        let bytes_b = output::encode(&header, &b, format).unwrap();
+        assert_eq!(bytes_a, bytes_b, "{format} bytes differ");
+        assert_eq!(output::checksum(&bytes_a), output::checksum(&bytes_b));
+    }
+
+    let parquet_a = columnar::to_parquet(&header, &a).unwrap();
+    let parquet_b = columnar::to_parquet(&header, &b).unwrap();
+    assert_eq!(parquet_a, parquet_b, "parquet bytes differ");
+    assert_eq!(output::checksum(&parquet_a), output::checksum(&parquet_b));
+
+    let json_a = output::encode(&header, &a, Format::Json).unwrap();
+    let json_b = output::encode(&header, &b, Format::Json).unwrap();
+    let checksum_a = envelope::open::<AllAccounts>(&json_a, "all_accounts")
+        .unwrap()
+        .header
+        .checksum;
+    let checksum_b = envelope::open::<AllAccounts>(&json_b, "all_accounts")
+        .unwrap()
+        .header
+        .checksum;
+    assert!(checksum_a.is_some());
+    assert_eq!(checksum_a, checksum_b);
+}
+
+#[test]
+fn ndjson_holds_only_records() {
+    let bytes = output::encode(&header(), &accounts(false), Format::Ndjson).unwrap();
+    let lines = bytes
+        .split_inclusive(|b| *b == b'\n')
+        .map(|line| serde_json::from_slice::<serde_json::Value>(line).unwrap())
+        .collect::<Vec<_>>();
+
+    // one line per account and name
+    assert_eq!(lines.len(), 6);
+    assert!(lines.iter().all(|line| line.get("account").is_some()));
+}
+
+#[tokio::test]
+async fn ndjson_writer_puts_the_header_next_to_the_file() {
+    let dir = std::env::temp_dir().join(format!("ndjson-writer-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir
+        .join("all_accounts.ndjson")
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    let accounts = accounts(false);
+    let mut writer = output::NdjsonWriter::create_at(path.clone(), &header(), Compression::Gzip)
+        .await
+        .unwrap();
+    for row in accounts.rows() {
+        writer.write(&row).unwrap();
+    }
+    assert_eq!(writer.finish().await.unwrap(), 6);
+
+    let records = output::read_file(&path).await.unwrap();
+    assert_eq!(
+        records,
+        output::encode(&header(), &accounts, Format::Ndjson).unwrap()
+    );
+
+    let sidecar = std::fs::read(format!("{path}.header.json")).unwrap();
+    let sidecar = serde_json::from_slice::<Header>(&sidecar).unwrap();
+    assert_eq!(sidecar.record_count, Some(6));
+    assert_eq!(sidecar.checksum, Some(output::checksum(&records)));
+
+    let file = std::fs::read(&path).unwrap();
+    let sha256 = std::fs::read_to_string(format!("{path}.sha256")).unwrap();
+    assert_eq!(
+        sha256,
+        format!("{}  all_accounts.ndjson\n", output::checksum(&file))
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn read_file_rejects_a_mislabelled_compression() {
+    let path = std::env::temp_dir()
+        .join(format!("mislabelled-{}.json.zst", std::process::id()))
+        .to_str()
+        .unwrap()
+        .to_string();
+    let bytes = output::encode(&header(), &accounts(false), Format::Json).unwrap();
+    std::fs::write(&path, Compression::Gzip.compress(&bytes).unwrap()).unwrap();
+
+    let err = output::read_file(&path).await.unwrap_err();
+    assert!(err.to_string().contains("zstd"), "{err}");
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn open_rejects_data_not_matching_the_checksum() {
+    let bytes = output::encode(&header(), &accounts(false), Format::Json).unwrap();
+    let tampered = String::from_utf8(bytes)
+        .unwrap()
+        .replace("carol.dot", "mallory.dot");
+
+    let err = envelope::open::<AllAccounts>(tampered.as_bytes(), "all_accounts").unwrap_err();
+    assert!(err.to_string().contains("checksum"), "{err}");
+}
@@ -0,0 +1,259 @@
+//! Applies a full and then an incremental batch to a Postgres mirror.
+//!
+//! Skipped unless `DATABASE_URL` is set. It must point at a scratch
+//! database: the mirror's tables are dropped first.
+use query::{
+    domain_events::{DomainEventKind, DomainEventRecord},
+    domains::DomainRecord,
+    meta::Meta,
+    mirror::{Mirror, SyncBatch},
+    registration_events::{RegistrationEventKind, RegistrationEventRecord},
+    registrations::{Record, Records},
+    transfers::TransferRecord,
+};
+use tokio_postgres::NoTls;
+
+const ALICE: &str = "0x0000000000000000000000000000000000000001";
+const BOB: &str = "0x0000000000000000000000000000000000000002";
+const CAROL: &str = "0x0000000000000000000000000000000000000003";
+
+fn domain_id(n: u8) -> String {
+    format!("0x{}", format!("{n:02x}").repeat(32))
+}
+
+fn meta(block_number: i32, deployment: &str) -> Meta {
+    Meta {
+        deployment: deployment.to_string(),
+        block_number,
+        block_hash: Some(format!("0x{block_number:064x}")),
+        block_timestamp: Some(1_667_908_800 + block_number),
+        has_indexing_errors: false,
+    }
+}
+
+fn domain(n: u8, owner: &str, subdomain_count: i32) -> DomainRecord {
+    DomainRecord {
+        id: domain_id(n),
+        name: Some(format!("name{n}.dot")),
+        label_name: Some(format!("name{n}")),
+        labelhash: None,
+        parent: None,
+        owner: Some(owner.to_string()),
+        resolved_address: None,
+        resolver: None,
+        subdomain_count,
+        created_at: 1_667_908_800,
+        expire: None,
+        capacity: None,
+    }
+}
+
+fn registration(n: u8, expire: i64) -> (String, Record) {
+    (
+        domain_id(n),
+        Record {
+            origin: domain_id(n),
+            expire: Some(expire),
+            capacity: 10,
+            children: 0,
+        },
+    )
+}
+
+fn registration_event(n: u8, block_number: i32, expire: i64) -> RegistrationEventRecord {
+    RegistrationEventRecord {
+        id: format!("registered-{n}-{block_number}"),
+        kind: RegistrationEventKind::NameRegistered,
+        domain: domain_id(n),
+        name: Some(format!("name{n}.dot")),
+        registrant: ALICE.to_string(),
+        block_number,
+        triggered_date: 1_667_908_800,
+        transaction_id: format!("0x{block_number:064x}"),
+        cost: Some("1000000000000000000".to_string()),
+        old_expiry: None,
+        new_expiry: Some(expire),
+        old_capacity: None,
+        new_capacity: Some(10),
+    }
+}
+
+fn transfer(n: u8, block_number: i32, from: Option<&str>, to: &str) -> TransferRecord {
+    TransferRecord {
+        id: format!("transfer-{n}-{block_number}"),
+        domain: domain_id(n),
+        name: Some(format!("name{n}.dot")),
+        from: from.map(str::to_string),
+        to: to.to_string(),
+        block_number,
+        triggered_date: 1_667_908_800,
+        transaction_id: format!("0x{block_number:064x}"),
+    }
+}
+
+fn domain_event(transfer: &TransferRecord) -> DomainEventRecord {
+    DomainEventRecord {
+        id: transfer.id.clone(),
+        domain: transfer.domain.clone(),
+        name: transfer.name.clone(),
+        block_number: transfer.block_number,
+        triggered_date: transfer.triggered_date,
+        transaction_id: transfer.transaction_id.clone(),
+        event: DomainEventKind::Transfer {
+            from: transfer.from.clone(),
+            to: transfer.to.clone(),
+        },
+    }
+}
+
+fn batch(meta: Meta, since: Option<i32>, domains: Vec<DomainRecord>) -> SyncBatch {
+    SyncBatch {
+        meta,
+        since,
+        domains,
+        registrations: Records(Default::default()),
+        registration_events: Vec::new(),
+        transfers: Vec::new(),
+        domain_events: Vec::new(),
+    }
+}
+
+async fn count(client: &tokio_postgres::Client, table: &str) -> i64 {
+    client
+        .query_one(&format!("SELECT count(*) FROM {table}"), &[])
+        .await
+        .unwrap()
+        .get(0)
+}
+
+#[tokio::test]
+async fn full_then_incremental_sync() {
+    let Ok(url) = std::env::var("DATABASE_URL") else {
+        eprintln!("DATABASE_URL is not set, skipping the mirror test");
+        return;
+    };
+
+    let (client, connection) = tokio_postgres::connect(&url, NoTls).await.unwrap();
+    tokio::spawn(connection);
+    client
+        .batch_execute(
+            "DROP TABLE IF EXISTS sync_state, accounts, domains, registrations,
+                registration_events, transfers, domain_events",
+        )
+        .await
+        .unwrap();
+
+    let mut mirror = Mirror::connect(&url).await.unwrap();
+    assert_eq!(mirror.last_synced_block().await.unwrap(), None);
+
+    // full: two names minted to alice
+    let mut full = batch(
+        meta(100, "QmTest"),
+        None,
+        vec![domain(1, ALICE, 0), domain(2, ALICE, 0)],
+    );
+    full.registrations = Records([registration(1, 2_000), registration(2, 3_000)].into());
+    full.registration_events = vec![
+        registration_event(1, 90, 2_000),
+        registration_event(2, 95, 3_000),
+    ];
+    full.transfers = vec![transfer(1, 90, None, ALICE), transfer(2, 95, None, ALICE)];
+    full.domain_events = full.transfers.iter().map(domain_event).collect();
+    mirror.apply(&full).await.unwrap();
+
+    assert_eq!(mirror.last_synced_block().await.unwrap(), Some(100));
+    assert_eq!(count(&client, "domains").await, 2);
+    assert_eq!(count(&client, "registrations").await, 2);
+    assert_eq!(count(&client, "registration_events").await, 2);
+    assert_eq!(count(&client, "transfers").await, 2);
+    assert_eq!(count(&client, "domain_events").await, 2);
+    assert_eq!(count(&client, "accounts").await, 1);
+
+    // incremental: name1 goes to bob and gets a subdomain owned by carol,
+    // name3 is new
+    let mut incremental = batch(
+        meta(200, "QmTest"),
+        Some(100),
+        vec![domain(1, BOB, 1), domain(3, BOB, 0)],
+    );
+    incremental.registrations = Records([registration(3, 4_000)].into());
+    incremental.registration_events = vec![registration_event(3, 150, 4_000)];
+    incremental.transfers = vec![
+        transfer(1, 120, Some(ALICE), BOB),
+        transfer(3, 150, None, BOB),
+    ];
+    incremental.domain_events = incremental.transfers.iter().map(domain_event).collect();
+    incremental.domain_events.push(DomainEventRecord {
+        id: "subdomain-1-130".to_string(),
+        event: DomainEventKind::NewSubdomain {
+            parent: domain_id(1),
+            name: "sub".to_string(),
+            to: CAROL.to_string(),
+        },
+        block_number: 130,
+        ..domain_event(&incremental.transfers[0])
+    });
+    mirror.apply(&incremental).await.unwrap();
+
+    let state = client
+        .query_one("SELECT block_number, deployment FROM sync_state", &[])
+        .await
+        .unwrap();
+    assert_eq!(state.get::<_, i32>(0), 200);
+    assert_eq!(state.get::<_, String>(1), "QmTest");
+    assert_eq!(count(&client, "domains").await, 3);
+    assert_eq!(count(&client, "transfers").await, 4);
+    assert_eq!(count(&client, "domain_events").await, 5);
+    assert_eq!(count(&client, "accounts").await, 3);
+    let registration_kind = client
+        .query_one(
+            "SELECT kind FROM registration_events WHERE domain = $1",
+            &[&domain_id(3)],
+        )
+        .await
+        .unwrap();
+    assert_eq!(registration_kind.get::<_, String>(0), "NameRegistered");
+
+    let name1 = client
+        .query_one(
+            "SELECT owner, synced_block FROM domains WHERE id = $1",
+            &[&domain_id(1)],
+        )
+        .await
+        .unwrap();
+    assert_eq!(name1.get::<_, String>(0), BOB);
+    assert_eq!(name1.get::<_, i32>(1), 200);
+    // the registration did not change, its children follow the domain
+    let children = client
+        .query_one(
+            "SELECT children FROM registrations WHERE domain = $1",
+            &[&domain_id(1)],
+        )
+        .await
+        .unwrap();
+    assert_eq!(children.get::<_, i32>(0), 1);
+    let kind = client
+        .query_one(
+            "SELECT kind, event->>'to' FROM domain_events WHERE id = $1",
+            &[&"transfer-1-120"],
+        )
+        .await
+        .unwrap();
+    assert_eq!(kind.get::<_, String>(0), "Transfer");
+    assert_eq!(kind.get::<_, String>(1), BOB);
+
+    // queried from the previous block, as if another sync got there first
+    let stale = batch(meta(300, "QmTest"), Some(100), vec![domain(2, BOB, 0)]);
+    assert!(mirror.apply(&stale).await.is_err());
+
+    let redeployed = batch(meta(300, "QmOther"), Some(200), vec![domain(2, BOB, 0)]);
+    let err = mirror.apply(&redeployed).await.unwrap_err();
+    assert!(err.to_string().contains("deployment"), "{err}");
+
+    assert_eq!(mirror.last_synced_block().await.unwrap(), Some(200));
+    let name2 = client
+        .query_one("SELECT owner FROM domains WHERE id = $1", &[&domain_id(2)])
+        .await
+        .unwrap();
+    assert_eq!(name2.get::<_, String>(0), ALICE);
+}
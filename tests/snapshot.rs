@@ -0,0 +1,86 @@
+//! A snapshot written to SQLite reads back with the same datasets, so an
+//! incremental export can start from it.
+use query::{
+    domains::DomainRecord,
+    meta::Meta,
+    registrations::Records,
+    resolvers::{KeyRecord, ResolverRecord},
+    snapshot::Snapshot,
+};
+
+const DOMAIN: &str = "0x1111111111111111111111111111111111111111111111111111111111111111";
+const RESOLVER: &str = "0x2222222222222222222222222222222222222222";
+
+fn key(key: Option<&str>, key_hash: &str, value: &str) -> KeyRecord {
+    KeyRecord {
+        key: key.map(str::to_string),
+        key_hash: key_hash.to_string(),
+        value: value.to_string(),
+        block_number: 90,
+    }
+}
+
+fn snapshot() -> Snapshot {
+    Snapshot {
+        meta: Meta {
+            deployment: "QmTest".to_string(),
+            block_number: 100,
+            block_hash: Some(format!("0x{}", "ab".repeat(32))),
+            block_timestamp: Some(1_667_908_800),
+            has_indexing_errors: false,
+        },
+        domains: vec![DomainRecord {
+            id: DOMAIN.to_string(),
+            name: Some("alice.dot".to_string()),
+            label_name: Some("alice".to_string()),
+            labelhash: None,
+            parent: None,
+            owner: Some(RESOLVER.to_string()),
+            resolved_address: None,
+            resolver: Some(RESOLVER.to_string()),
+            subdomain_count: 0,
+            created_at: 1_667_908_800,
+            expire: None,
+            capacity: None,
+        }],
+        registrations: Records(Default::default()),
+        subdomain_events: Vec::new(),
+        transfers: Vec::new(),
+        resolvers: vec![ResolverRecord {
+            domain: DOMAIN.to_string(),
+            name: Some("alice.dot".to_string()),
+            resolver: RESOLVER.to_string(),
+            addr: Some(RESOLVER.to_string()),
+            coin_types: vec!["60".to_string()],
+            keys: vec!["avatar".to_string(), "url".to_string()],
+            texts: vec![
+                key(Some("avatar"), "1", "ipfs://avatar"),
+                key(None, "2", "unknown key"),
+            ],
+            links: vec![key(None, "3", "4")],
+            content_hash: None,
+            content_uri: None,
+        }],
+    }
+}
+
+#[test]
+fn sqlite_round_trip_keeps_resolvers() {
+    let path = std::env::temp_dir()
+        .join(format!("snapshot-round-trip-{}.sqlite", std::process::id()))
+        .to_str()
+        .unwrap()
+        .to_string();
+    let _ = std::fs::remove_file(&path);
+
+    let written = snapshot();
+    written.write_sqlite(&written.header(), &path).unwrap();
+    let read = Snapshot::read_sqlite(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(read.resolvers.len(), 1);
+    assert_eq!(read.resolvers[0].keys, ["avatar", "url"]);
+    assert_eq!(read.resolvers[0].texts.len(), 2);
+    assert_eq!(read.resolvers[0].links[0].value, "4");
+    assert_eq!(read.checksum().unwrap(), written.checksum().unwrap());
+}